@@ -0,0 +1,137 @@
+// Lox 源码格式化工具
+//
+//     loxfmt [--check] [--indent <n> | --tabs] [--width <n>] [file...]
+//
+// 不指定文件时从标准输入读取，结果写到标准输出。
+// --check 只检查不修改，未格式化的文件名输出到标准错误，存在这样的文件时退出码为 1，
+// 适合在 CI 中使用。
+
+use std::{
+    fs,
+    io::{self, Read},
+    process,
+};
+
+use crafting::{
+    diagnostic::{Diagnostic, Style},
+    formatter::Formatter,
+};
+
+/// sysexits.h 中的退出码
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_IOERR: i32 = 74;
+
+const USAGE: &str = "usage: loxfmt [--check] [--indent <n> | --tabs] [--width <n>] [file...]";
+
+struct Options {
+    check: bool,
+    formatter: Formatter,
+    files: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        check: false,
+        formatter: Formatter::new(),
+        files: vec![],
+    };
+    while let Some(arg) = args.next() {
+        let mut number = |name: &str| -> Result<usize, String> {
+            args.next()
+                .and_then(|v| v.parse().ok())
+                .ok_or(format!("{} expects a number", name))
+        };
+        match arg.as_str() {
+            "--check" => options.check = true,
+            "--tabs" => options.formatter = options.formatter.with_indent("\t"),
+            "--indent" => {
+                let n = number("--indent")?;
+                options.formatter = options.formatter.with_indent(" ".repeat(n));
+            }
+            "--width" => {
+                let n = number("--width")?;
+                options.formatter = options.formatter.with_max_width(n);
+            }
+            "-h" | "--help" => return Err(String::new()),
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown option '{}'", flag))
+            }
+            file => options.files.push(file.to_string()),
+        }
+    }
+    Ok(options)
+}
+
+fn report(file: &str, source: &str, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprint!("{}", diagnostic.render(file, source, Style::Plain));
+    }
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("error: {}", message);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(EX_USAGE);
+        }
+    };
+
+    // 标准输入
+    if options.files.is_empty() || options.files == ["-"] {
+        let mut source = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut source) {
+            eprintln!("error: can't read stdin: {}", e);
+            process::exit(EX_IOERR);
+        }
+        match options.formatter.format(&source) {
+            Ok(formatted) if options.check => {
+                if formatted != source {
+                    eprintln!("<stdin> is not formatted");
+                    process::exit(1);
+                }
+            }
+            Ok(formatted) => print!("{}", formatted),
+            Err(diagnostics) => {
+                report("<stdin>", &source, &diagnostics);
+                process::exit(EX_DATAERR);
+            }
+        }
+        return;
+    }
+
+    let mut code = 0;
+    for file in &options.files {
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("error: can't read {}: {}", file, e);
+                code = EX_IOERR;
+                continue;
+            }
+        };
+        let formatted = match options.formatter.format(&source) {
+            Ok(formatted) => formatted,
+            Err(diagnostics) => {
+                report(file, &source, &diagnostics);
+                code = code.max(EX_DATAERR);
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if options.check {
+            eprintln!("{} is not formatted", file);
+            code = code.max(1);
+        } else if let Err(e) = fs::write(file, formatted) {
+            eprintln!("error: can't write {}: {}", file, e);
+            code = EX_IOERR;
+        }
+    }
+    process::exit(code);
+}
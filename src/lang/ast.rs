@@ -1,63 +1,90 @@
 // http://www.craftinginterpreters.com/appendix-ii.html
 
-use super::token::{Object, Token, TokenType};
+use super::token::{Object, Span, Token};
 
 #[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 /// Expr
 pub enum Expression {
-    Literal(Object),
-    Assignment(Token, Box<Expression>),
+    Literal(Object, Span),
+    /// 最后一项是 Resolver 计算出的作用域深度，None 表示全局变量
+    Assignment(Token, Box<Expression>, Option<usize>),
     Unary(Token, Box<Expression>),
     Binary(Box<Expression>, Token, Box<Expression>),
-    Grouping(Box<Expression>),
+    /// 包含左右括号的 span
+    Grouping(Box<Expression>, Span),
     /// A and B, A or B
     Logical(Box<Expression>, Token, Box<Expression>),
     Call(Box<Expression>, Token, Vec<Expression>),
-    Var(Token),
+    /// 最后一项是 Resolver 计算出的作用域深度，None 表示全局变量
+    Var(Token, Option<usize>),
     Mark,
 }
 
 impl Expression {
-    fn walk<F>(&self, visitor: &F) -> bool
-    where
-        F: Fn(&Expression) -> bool,
-    {
-        visitor(self)
-            && match self {
-                Expression::Literal(object) => {
-                    println!("{:?}", object);
-                    false
-                }
-                Expression::Assignment(token, exp) => {
-                    println!("{:?}", token);
-                    exp.walk(visitor)
-                }
-                Expression::Unary(_, _) => todo!(),
-                Expression::Binary(_, _, _) => todo!(),
-                Expression::Grouping(_) => todo!(),
-                Expression::Logical(_, _, _) => todo!(),
-                Expression::Mark => todo!(),
-                Expression::Var(_) => todo!(),
-                Expression::Call(callee, paren, arguments) => todo!(),
+    /// 表达式在源码中覆盖的区间
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Literal(_, span) | Expression::Grouping(_, span) => *span,
+            Expression::Assignment(name, value, _) => name.span.to(value.span()),
+            Expression::Unary(op, right) => op.span.to(right.span()),
+            Expression::Binary(left, _, right) | Expression::Logical(left, _, right) => {
+                left.span().to(right.span())
             }
+            Expression::Call(callee, paren, _) => callee.span().to(paren.span),
+            Expression::Var(name, _) => name.span,
+            Expression::Mark => Span::default(),
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+/// 语句的 span 从第一个 token 开始，不包含结尾的分号
+#[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
-    Print(Expression),
+    Print(Expression, Span),
     Expression(Expression),
-    Var(Token, Expression),
-    Block(Vec<Statement>),
-    If(Expression, Box<Statement>, Option<Box<Statement>>),
-    Function(Token, Vec<Token>, Vec<Statement>),
+    /// 没有初始值时为 nil
+    Var(Token, Option<Expression>, Span),
+    /// 包含左右花括号的 span
+    Block(Vec<Statement>, Span),
+    /// if 关键字, 条件, then, else
+    If(Token, Expression, Box<Statement>, Option<Box<Statement>>),
+    Function(Token, Vec<Token>, Vec<Statement>, Span),
+    /// return 关键字, 返回值
+    Return(Token, Option<Expression>),
+    /// while 关键字, 条件, 循环体。for 循环在解析时转换为 while
+    While(Token, Expression, Box<Statement>),
 }
 
-impl Statement {}
+impl Statement {
+    /// 语句在源码中覆盖的区间
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Print(_, span)
+            | Statement::Var(_, _, span)
+            | Statement::Block(_, span)
+            | Statement::Function(_, _, _, span) => *span,
+            Statement::Expression(expr) => expr.span(),
+            Statement::While(keyword, _, body) => keyword.span.to(body.span()),
+            Statement::Return(keyword, value) => match value {
+                Some(value) => keyword.span.to(value.span()),
+                None => keyword.span,
+            },
+            Statement::If(keyword, _, then_stmt, else_stmt) => match else_stmt {
+                Some(else_stmt) => keyword.span.to(else_stmt.span()),
+                None => keyword.span.to(then_stmt.span()),
+            },
+        }
+    }
+}
 
 #[test]
 fn expression_display() {
+    use super::{
+        token::TokenType,
+        visitor::{walk_expr, ExprVisitor},
+    };
+
     let e = Expression::Assignment(
         Token::new(
             TokenType::IDENTIFIER,
@@ -65,15 +92,22 @@ fn expression_display() {
             Object::String("name".to_string()),
             1,
         ),
-        Box::new(Expression::Literal(Object::Digit(1.0))),
+        Box::new(Expression::Literal(Object::Digit(1.0), Span::default())),
+        None,
     );
 
-    let mut i = 0;
-    e.walk(&|expr| {
-        if i > 20 {
-            return false;
+    // 遍历到赋值和右侧的字面量
+    struct Count(usize);
+    impl<'ast> ExprVisitor<'ast> for Count {
+        type Output = ();
+
+        fn visit_expr(&mut self, expr: &'ast Expression) {
+            self.0 += 1;
+            walk_expr(self, expr);
         }
-        // i += 1;
-        true
-    });
+    }
+
+    let mut count = Count(0);
+    count.visit_expr(&e);
+    assert_eq!(count.0, 2);
 }
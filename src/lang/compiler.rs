@@ -1,176 +1,295 @@
 // http://www.craftinginterpreters.com/appendix-i.html
 
-use std::{borrow::Borrow, cell::RefCell, collections::btree_set::Union, env, rc::Rc};
-
-use crate::lang::environment;
+use std::{cell::RefCell, rc::Rc};
 
 use super::{
     ast::{Expression, Statement},
-    environment::Environment,
-    token::{Object, TokenType::*, UnionObject},
+    environment::{self, Environment},
+    error::RuntimeError,
+    operator,
+    token::{Closure, Native, Object, Token, TokenType::*, UnionObject},
+    visitor::{ExprVisitor, StmtVisitor},
 };
 
+/// 语句执行被打断的原因：运行时错误，或者 return 携带返回值一路向上
+pub enum Unwind<'a> {
+    Error(RuntimeError),
+    Return(Rc<UnionObject<'a>>),
+}
+
+impl<'a> From<RuntimeError> for Unwind<'a> {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
 #[derive(Debug)]
 pub struct Compiler<'a> {
     // pub expr: Expression,
     environment: Rc<RefCell<Environment<'a>>>,
+    /// 最外层的全局作用域，未被 Resolver 解析到的变量都在这里查找
+    globals: Rc<RefCell<Environment<'a>>>,
 }
 
-#[allow(dead_code)]
 impl<'a> Compiler<'a> {
-    fn new() -> Self {
+    pub fn new() -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new(
+            Option::<Rc<RefCell<Environment>>>::None,
+        )));
         Compiler {
-            environment: Rc::new(RefCell::new(Environment::new(
-                Option::<Rc<RefCell<Environment>>>::None,
-            ))),
+            environment: globals.clone(),
+            globals,
+        }
+    }
+
+    /// 执行经过 Resolver 处理的语句
+    pub fn interpret(&mut self, statements: Vec<Statement>) -> Result<(), RuntimeError> {
+        for stmt in &statements {
+            match self.visit_stmt(stmt) {
+                Err(Unwind::Error(error)) => return Err(error),
+                // 顶层的 return 直接结束执行
+                Err(Unwind::Return(_)) => return Ok(()),
+                Ok(()) => {}
+            }
         }
+        Ok(())
     }
 
-    fn interpret(&'a mut self, statements: Vec<Statement>) {
-        for stmt in statements {
-            self.compile_stmt(stmt);
+    /// 在当前环境中求值一个经过 Resolver 处理的表达式，REPL 用它输出结果
+    pub fn evaluate(&mut self, expr: &Expression) -> Result<Rc<UnionObject<'a>>, RuntimeError> {
+        self.visit_expr(expr)
+    }
+
+    /// 全局作用域中的全部绑定，按名字排序
+    pub fn globals(&self) -> Vec<(String, Rc<UnionObject<'a>>)> {
+        let mut globals: Vec<_> = self
+            .globals
+            .borrow()
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        globals.sort_by(|a, b| a.0.cmp(&b.0));
+        globals
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Rc<UnionObject<'a>>> {
+        self.globals.borrow().values.get(name).cloned()
+    }
+
+    /// 定义或者覆盖一个全局变量
+    pub fn define_global(&mut self, name: &str, value: Rc<UnionObject<'a>>) {
+        self.globals.borrow_mut().define(name.to_string(), value);
+    }
+
+    /// 调用本地函数，函数返回的错误信息作为运行时错误报告在调用处
+    fn call_native(
+        &mut self,
+        native: &Native<'a>,
+        paren: &Token,
+        arguments: &[Expression],
+    ) -> Result<Rc<UnionObject<'a>>, RuntimeError> {
+        if native.arity != arguments.len() {
+            return Err(RuntimeError::new(
+                paren.clone(),
+                format!(
+                    "Expected {} arguments but got {}",
+                    native.arity,
+                    arguments.len()
+                ),
+            ));
+        }
+        let mut args = Vec::with_capacity(arguments.len());
+        for expr in arguments {
+            args.push(self.visit_expr(expr)?);
         }
+        (native.function)(&args).map_err(|message| RuntimeError::new(paren.clone(), message))
     }
 
-    fn compile_expr(&mut self, expr: Expression) -> Rc<UnionObject<'a>> {
+    /// 在给定的环境中执行语句，无论是否出错都会恢复之前的环境
+    fn execute_block(
+        &mut self,
+        statements: &[Statement],
+        environment: Environment<'a>,
+    ) -> Result<(), Unwind<'a>> {
+        let previous = self.environment.clone();
+        self.environment = Rc::new(RefCell::new(environment));
+        let result = self.visit_stmts(statements);
+        self.environment = previous;
+        result
+    }
+}
+
+impl<'a, 'ast> ExprVisitor<'ast> for Compiler<'a> {
+    type Output = Result<Rc<UnionObject<'a>>, RuntimeError>;
+
+    fn visit_expr(&mut self, expr: &'ast Expression) -> Self::Output {
         match expr {
-            Expression::Literal(v) => match v {
-                v => v.into(),
-            },
+            Expression::Literal(v, _) => Ok(v.clone().into()),
             Expression::Unary(token, ex) => {
-                let ret = self.compile_expr(*ex);
-                if let UnionObject::Value(Object::Digit(n)) = ret.as_ref() {
-                    if token.tag == MINUS {
-                        return Object::from(-n).into();
-                    }
-                }
-
-                if let UnionObject::Value(Object::Bool(n)) = ret.as_ref() {
-                    if token.tag == BANG {
-                        return Object::from(!n).into();
+                let value = self.visit_expr(ex)?;
+                let operator = match operator::unary(token.tag) {
+                    Some(operator) => operator,
+                    None => return Err(RuntimeError::new(token.clone(), "Unknown unary operator")),
+                };
+                operator(&value)
+                    .map(Object::into)
+                    .map_err(|message| RuntimeError::new(token.clone(), message))
+            }
+            Expression::Assignment(ident, exp, depth) => {
+                let value = self.visit_expr(exp)?;
+                match depth {
+                    Some(distance) => {
+                        environment::assign_at(&self.environment, *distance, ident, value.clone())?
                     }
+                    None => self.globals.borrow_mut().assign(ident, value.clone())?,
                 }
-
-                panic!("expected expression")
-            }
-            Expression::Assignment(ident, exp) => {
-                let value = self.compile_expr(*exp);
-                self.environment
-                    .borrow_mut()
-                    .assign(ident.lexeme, value.clone());
-                value
+                Ok(value)
             }
             Expression::Binary(le, op, re) => {
-                let left = self.compile_expr(*le).into();
-                let right = self.compile_expr(*re).into();
-
-                if let (Object::Digit(lv), Object::Digit(rv)) = (left, right) {
-                    return match op.tag {
-                        // TODO: 使用 Operator overload
-                        PLUS => Object::Digit(lv + rv),
-                        MINUS => Object::Digit(lv - rv),
-                        SLASH => Object::Digit(lv / rv),
-                        STAR => Object::Digit(lv * rv),
-                        GREATER => Object::Bool(lv > rv),
-                        GREATER_EQUAL => Object::Bool(lv >= rv),
-                        LESS => Object::Bool(lv < rv),
-                        LESS_EQUAL => Object::Bool(lv <= rv),
-                        // TODO：string 的比较
-                        BANG_EQUAL => Object::Bool(lv != rv),
-                        EQUAL => Object::Bool(lv == rv),
-                        _ => {
-                            panic!("expected expression")
-                        }
-                    }
-                    .into();
+                let left = self.visit_expr(le)?;
+                let right = self.visit_expr(re)?;
+                let operator = match operator::binary(op.tag) {
+                    Some(operator) => operator,
+                    None => return Err(RuntimeError::new(op.clone(), "Unknown binary operator")),
+                };
+                operator(&left, &right)
+                    .map(Object::into)
+                    .map_err(|message| RuntimeError::new(op.clone(), message))
+            }
+            Expression::Grouping(ex, _) => self.visit_expr(ex),
+            Expression::Logical(le, op, re) => {
+                let left = self.visit_expr(le)?;
+                // 短路求值，返回决定结果的那个操作数
+                let decided = match op.tag {
+                    OR => left.is_truthy(),
+                    _ => !left.is_truthy(),
+                };
+                if decided {
+                    Ok(left)
+                } else {
+                    self.visit_expr(re)
                 }
-
-                panic!("expected expression")
             }
-            Expression::Grouping(ex) => self.compile_expr(*ex),
-            Expression::Logical(_, _, _) => todo!(),
             Expression::Mark => todo!(),
-            Expression::Var(token) => self.environment.borrow_mut().retrieve(token.lexeme).clone(),
-            Expression::Call(callee, _, arguments) => {
-                if let UnionObject::Function(function) = self.compile_expr(*callee).borrow() {
-                    if let Statement::Function(a, parameters, stmts) = function.borrow() {
-                        // 参数计算
-                        let mut args = Vec::<Rc<UnionObject>>::new();
-                        for expr in arguments {
-                            args.push(self.compile_expr(expr))
-                        }
-
-                        // 补充上下文
-                        let mut environment = Environment::new(self.environment.clone());
-                        for (i, name) in parameters.iter().enumerate() {
-                            environment.define(name.lexeme.clone(), args[i].clone());
-                        }
-                        let prev = self.environment.clone();
-                        self.environment = Rc::new(RefCell::new(environment));
-                        for stmt in stmts {
-                            self.compile_stmt(stmt.to_owned());
-                        }
-                        self.environment = prev;
+            Expression::Var(token, depth) => match depth {
+                Some(distance) => environment::get_at(&self.environment, *distance, token),
+                None => self.globals.borrow_mut().retrieve(token),
+            },
+            Expression::Call(callee, paren, arguments) => {
+                let callee = self.visit_expr(callee)?;
+                let function = match callee.as_ref() {
+                    UnionObject::Function(function) => function,
+                    UnionObject::Native(native) => {
+                        return self.call_native(native, paren, arguments)
                     }
+                    _ => return Err(RuntimeError::new(paren.clone(), "Can only call functions")),
+                };
+
+                if let Statement::Function(_, parameters, stmts, _) = function.declaration.as_ref()
+                {
+                    if parameters.len() != arguments.len() {
+                        return Err(RuntimeError::new(
+                            paren.clone(),
+                            format!(
+                                "Expected {} arguments but got {}",
+                                parameters.len(),
+                                arguments.len()
+                            ),
+                        ));
+                    }
+
+                    // 参数计算
+                    let mut args = Vec::<Rc<UnionObject>>::new();
+                    for expr in arguments {
+                        args.push(self.visit_expr(expr)?)
+                    }
+
+                    // 以声明时的环境作为外层作用域
+                    let mut environment = Environment::new(function.closure.clone());
+                    for (name, value) in parameters.iter().zip(args) {
+                        environment.define(name.lexeme.clone(), value);
+                    }
+                    return match self.execute_block(stmts, environment) {
+                        Ok(()) => Ok(Object::Nil.into()),
+                        Err(Unwind::Return(value)) => Ok(value),
+                        Err(Unwind::Error(error)) => Err(error),
+                    };
                 }
-                // TODO: return statement
-                Object::Bool(false).into()
+                Ok(Object::Nil.into())
             }
         }
     }
+}
 
-    // fn call_func(&mut self, )
+impl<'a, 'ast> StmtVisitor<'ast> for Compiler<'a> {
+    type StmtOutput = Result<(), Unwind<'a>>;
 
-    fn compile_stmt(&mut self, stmt: Statement) {
+    fn visit_stmt(&mut self, stmt: &'ast Statement) -> Self::StmtOutput {
         match stmt {
             Statement::Expression(expr) => {
-                self.compile_expr(expr);
+                self.visit_expr(expr)?;
             }
-            Statement::Print(expr) => {
-                let value = self.compile_expr(expr);
-                println!("{}", <Rc<UnionObject<'a>> as Into<Object>>::into(value))
+            Statement::Print(expr, _) => {
+                let value = self.visit_expr(expr)?;
+                println!("{}", value)
             }
-            Statement::Var(name, initializer) => {
-                let value = self.compile_expr(initializer);
-                self.environment.borrow_mut().define(name.lexeme, value);
+            Statement::Var(name, initializer, _) => {
+                let value = match initializer {
+                    Some(expr) => self.visit_expr(expr)?,
+                    None => Object::Nil.into(),
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(name.lexeme.clone(), value);
             }
-            Statement::Block(statements) => {
-                let previous = self.environment.clone();
+            Statement::Block(statements, _) => {
                 let inner = Environment::new(self.environment.clone());
-                self.environment = Rc::new(RefCell::new(inner));
-                for stmt in statements {
-                    self.compile_stmt(stmt);
-                }
-
-                self.environment = previous;
+                self.execute_block(statements, inner)?;
             }
-            Statement::If(condition, then_stmt, else_stmt) => {
-                if let Object::Bool(truty) = self.compile_expr(condition).into() {
-                    if truty {
-                        self.compile_stmt(*then_stmt)
-                    } else if let Some(else_stmt) = else_stmt {
-                        self.compile_stmt(*else_stmt)
-                    }
-                } else {
-                    panic!("expect a bool within if statement")
+            Statement::If(_, condition, then_stmt, else_stmt) => {
+                if self.visit_expr(condition)?.is_truthy() {
+                    self.visit_stmt(then_stmt)?
+                } else if let Some(else_stmt) = else_stmt {
+                    self.visit_stmt(else_stmt)?
                 }
             }
-            function => {
-                if let Statement::Function(name, ..) = &function {
-                    self.environment.borrow_mut().define(
-                        (*name).lexeme.clone(),
-                        Rc::new(UnionObject::Function(Rc::new(function))),
-                    );
+            // 条件和循环体按引用执行，每次迭代不复制 AST
+            Statement::While(_, condition, body) => {
+                while self.visit_expr(condition)?.is_truthy() {
+                    self.visit_stmt(body)?;
                 }
             }
+            Statement::Return(_, value) => {
+                let value = match value {
+                    Some(expr) => self.visit_expr(expr)?,
+                    None => Object::Nil.into(),
+                };
+                return Err(Unwind::Return(value));
+            }
+            Statement::Function(name, ..) => {
+                let closure = Closure {
+                    declaration: Rc::new(stmt.clone()),
+                    closure: self.environment.clone(),
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(name.lexeme.clone(), Rc::new(UnionObject::Function(closure)));
+            }
         };
+        Ok(())
+    }
+}
+
+impl<'a> Default for Compiler<'a> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[test]
 fn test() {
     use super::{lexer::Lexer, parser::Parser};
-    // FIXME: Option Unwrap Error
     let mut l = Lexer::new(String::from(
         "function a(){
             print 1
@@ -180,12 +299,359 @@ fn test() {
 
         ",
     ));
-    l.scan_tokens();
+    let (tokens, _) = l.scan_tokens();
 
-    let mut parser = Parser::new(l.tokens);
-    let statements = parser.parse();
+    let mut parser = Parser::new(tokens);
+    let (statements, _) = parser.parse();
     println!("{:?}", statements);
 
-    let _ = Compiler::new().interpret(statements);
+    Compiler::new().interpret(statements).unwrap();
     // assert_eq!(result, Object::Digit(3.0));
 }
+
+#[cfg(test)]
+fn run(source: &str) -> Result<Compiler<'static>, RuntimeError> {
+    use super::{lexer::Lexer, parser::Parser, resolver::Resolver};
+
+    let (tokens, _) = Lexer::new(String::from(source)).scan_tokens();
+    let (mut statements, errors) = Parser::new(tokens).parse();
+    assert!(errors.is_empty(), "{:?}", errors);
+    let errors = Resolver::new().resolve(&mut statements);
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let mut compiler = Compiler::new();
+    compiler.interpret(statements)?;
+    Ok(compiler)
+}
+
+/// 忽略静态检查的错误直接执行，用来测试解释器自身报告的错误
+#[cfg(test)]
+fn run_unchecked(source: &str) -> Result<Compiler<'static>, RuntimeError> {
+    use super::{lexer::Lexer, parser::Parser, resolver::Resolver};
+
+    let (tokens, _) = Lexer::new(String::from(source)).scan_tokens();
+    let (mut statements, errors) = Parser::new(tokens).parse();
+    assert!(errors.is_empty(), "{:?}", errors);
+    Resolver::new().resolve(&mut statements);
+
+    let mut compiler = Compiler::new();
+    compiler.interpret(statements)?;
+    Ok(compiler)
+}
+
+#[cfg(test)]
+impl<'a> Compiler<'a> {
+    fn global(&self, name: &str) -> Object {
+        use std::convert::TryFrom;
+
+        Object::try_from(self.environment.borrow().values[name].clone()).unwrap()
+    }
+}
+
+#[test]
+fn runtime_error() {
+    let error = run_unchecked("var a = 1;\n{\n  print foo;\n}").unwrap_err();
+    assert_eq!(error.token.lexeme, "foo");
+    assert_eq!(error.to_string(), "[line 3] Undefined variable 'foo'");
+
+    let error = run("print -true;").unwrap_err();
+    assert_eq!(error.to_string(), "[line 1] Operand must be a number");
+
+    let error = run_unchecked("function f(a) { print a; }\nf(1, 2);").unwrap_err();
+    assert_eq!(error.to_string(), "[line 2] Expected 1 arguments but got 2");
+
+    assert!(run_unchecked("var a = 1;\n{\n  a = 2;\n}\nb = 3;").is_err());
+
+    // 静态检查无法发现的情况仍然在运行时报告
+    let error = run("fun f() { print foo; }\nf();\nvar foo = 1;").unwrap_err();
+    assert_eq!(error.to_string(), "[line 1] Undefined variable 'foo'");
+    let error = run("function f(a) { print a; }\nvar g = f;\ng(1, 2);").unwrap_err();
+    assert_eq!(error.to_string(), "[line 3] Expected 1 arguments but got 2");
+}
+
+#[test]
+fn return_value() {
+    let compiler = run("
+        function sign(n) {
+            if (n < 0) {
+                {
+                    return -1;
+                }
+            }
+            if (n > 0) return 1;
+            return 0;
+        }
+        function nothing() {
+            var local = 1;
+            return;
+            local = 2;
+        }
+
+        var a = sign(-5);
+        var b = sign(3);
+        var c = sign(0);
+        var d = nothing();
+    ")
+    .unwrap();
+
+    assert_eq!(compiler.global("a"), Object::Digit(-1.0));
+    assert_eq!(compiler.global("b"), Object::Digit(1.0));
+    assert_eq!(compiler.global("c"), Object::Digit(0.0));
+    assert_eq!(compiler.global("d"), Object::Nil);
+    // 提前返回之后回到调用方的环境
+    assert!(!compiler.environment.borrow().values.contains_key("local"));
+}
+
+#[test]
+fn closure() {
+    let compiler = run("
+        fun makeCounter() {
+            var i = 0;
+            fun count() {
+                i = i + 1;
+                return i;
+            }
+            return count;
+        }
+
+        var counter = makeCounter();
+        var other = makeCounter();
+        var a = counter();
+        var b = counter();
+        var c = other();
+
+        var x = 1;
+        fun show() { return x; }
+        fun shadow() {
+            var x = 2;
+            return show();
+        }
+        var d = shadow();
+    ")
+    .unwrap();
+
+    assert_eq!(compiler.global("a"), Object::Digit(1.0));
+    assert_eq!(compiler.global("b"), Object::Digit(2.0));
+    assert_eq!(compiler.global("c"), Object::Digit(1.0));
+    // 词法作用域：show 看到的是全局的 x
+    assert_eq!(compiler.global("d"), Object::Digit(1.0));
+}
+
+#[test]
+fn resolved_binding() {
+    // 闭包捕获的是声明时可见的变量，之后同名的局部变量不影响它
+    let compiler = run("
+        var a = \"global\";
+        var r1;
+        var r2;
+        {
+            fun showA() { return a; }
+            r1 = showA();
+            var a = \"block\";
+            r2 = showA();
+        }
+
+        var b = 1;
+        {
+            var b = 2;
+            b = 3;
+        }
+    ")
+    .unwrap();
+
+    assert_eq!(compiler.global("r1"), Object::String("global".to_string()));
+    assert_eq!(compiler.global("r2"), Object::String("global".to_string()));
+    assert_eq!(compiler.global("b"), Object::Digit(1.0));
+}
+
+#[test]
+fn loops() {
+    let compiler = run("
+        var sum = 0;
+        var i = 0;
+        while (i < 5) {
+            sum = sum + i;
+            i = i + 1;
+        }
+
+        var product = 1;
+        for (var j = 1; j <= 5; j = j + 1) product = product * j;
+
+        var k = 0;
+        for (; k < 3;) k = k + 1;
+
+        fun find(limit) {
+            for (var n = 0; true; n = n + 1) {
+                while (true) {
+                    if (n * n > limit) return n;
+                    n = n + 1;
+                }
+            }
+        }
+        var found = find(10);
+    ")
+    .unwrap();
+
+    assert_eq!(compiler.global("sum"), Object::Digit(10.0));
+    assert_eq!(compiler.global("product"), Object::Digit(120.0));
+    assert_eq!(compiler.global("k"), Object::Digit(3.0));
+    assert_eq!(compiler.global("found"), Object::Digit(4.0));
+    // for 的初始化变量只在循环内可见
+    assert!(run_unchecked("for (var j = 0; j < 1; j = j + 1) {} print j;").is_err());
+}
+
+#[test]
+fn logical() {
+    let compiler = run("
+        var calls = 0;
+        fun touch(v) {
+            calls = calls + 1;
+            return v;
+        }
+
+        var a = 1 or touch(2);
+        var b = false or 3;
+        var c = false and touch(4);
+        var d = true and 5;
+        var e = 1 < 2 and 2 < 3 or false;
+        var f = false or false and touch(6);
+    ")
+    .unwrap();
+
+    assert_eq!(compiler.global("a"), Object::Digit(1.0));
+    assert_eq!(compiler.global("b"), Object::Digit(3.0));
+    assert_eq!(compiler.global("c"), Object::Bool(false));
+    assert_eq!(compiler.global("d"), Object::Digit(5.0));
+    assert_eq!(compiler.global("e"), Object::Bool(true));
+    assert_eq!(compiler.global("f"), Object::Bool(false));
+    assert_eq!(compiler.global("calls"), Object::Digit(0.0));
+}
+
+#[test]
+fn nil_and_truthiness() {
+    let compiler = run("
+        var a;
+        var b = nil;
+        var c = !nil;
+        var d = !0;
+        var e = \"\" or 1;
+        var f = nil or \"default\";
+
+        var branch;
+        if (0) branch = \"zero is truthy\"; else branch = \"zero is falsey\";
+
+        var count = 0;
+        var items = 3;
+        while (items) {
+            count = count + 1;
+            if (count > 2) items = nil;
+        }
+    ")
+    .unwrap();
+
+    assert_eq!(compiler.global("a"), Object::Nil);
+    assert_eq!(compiler.global("b"), Object::Nil);
+    assert_eq!(compiler.global("c"), Object::Bool(true));
+    assert_eq!(compiler.global("d"), Object::Bool(false));
+    assert_eq!(compiler.global("e"), Object::String("".to_string()));
+    assert_eq!(compiler.global("f"), Object::String("default".to_string()));
+    assert_eq!(
+        compiler.global("branch"),
+        Object::String("zero is truthy".to_string())
+    );
+    assert_eq!(compiler.global("count"), Object::Digit(3.0));
+}
+
+#[test]
+fn strings_and_equality() {
+    let compiler = run("
+        var greeting = \"hello\" + \", \" + \"world\";
+        fun f() {}
+        fun g() {}
+        var h = f;
+
+        var a = \"a\" != \"b\";
+        var b = 1 != \"1\";
+        var c = nil != false;
+        var d = f != g;
+        var e = f != h;
+        var n = nil != nil;
+    ")
+    .unwrap();
+
+    assert_eq!(
+        compiler.global("greeting"),
+        Object::String("hello, world".to_string())
+    );
+    assert_eq!(compiler.global("a"), Object::Bool(true));
+    assert_eq!(compiler.global("b"), Object::Bool(true));
+    assert_eq!(compiler.global("c"), Object::Bool(true));
+    assert_eq!(compiler.global("d"), Object::Bool(true));
+    assert_eq!(compiler.global("e"), Object::Bool(false));
+    assert_eq!(compiler.global("n"), Object::Bool(false));
+
+    let error = run("var x = \"a\" - 1;").unwrap_err();
+    assert_eq!(error.to_string(), "[line 1] Operands must be numbers");
+    let error = run("var x = \"a\" + 1;").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "[line 1] Operands must be two numbers or two strings"
+    );
+    assert!(run("fun f() {} var x = f < 1;").is_err());
+
+    // == 适用于任意两种类型，只有同一个值才相等
+    let values = ["1", "2", "\"1\"", "\"a\"", "true", "false", "nil", "f", "g"];
+    let pair =
+        |i: usize, j: usize| format!("e{}{}", (b'a' + i as u8) as char, (b'a' + j as u8) as char);
+    let mut source = String::from("fun f() {} fun g() {}\n");
+    for (i, left) in values.iter().enumerate() {
+        for (j, right) in values.iter().enumerate() {
+            source.push_str(&format!("var {} = {} == {};\n", pair(i, j), left, right));
+        }
+    }
+    let compiler = run(&source).unwrap();
+    for i in 0..values.len() {
+        for j in 0..values.len() {
+            assert_eq!(
+                compiler.global(&pair(i, j)),
+                Object::Bool(i == j),
+                "{} == {}",
+                values[i],
+                values[j]
+            );
+        }
+    }
+}
+
+#[test]
+fn equality() {
+    let compiler = run("
+        var a = 1 == 1;
+        var b = \"a\" == \"a\";
+        var c = nil == false;
+        var d = 1 + 1 == 2 and 2 != 3;
+    ")
+    .unwrap();
+
+    assert_eq!(compiler.global("a"), Object::Bool(true));
+    assert_eq!(compiler.global("b"), Object::Bool(true));
+    assert_eq!(compiler.global("c"), Object::Bool(false));
+    assert_eq!(compiler.global("d"), Object::Bool(true));
+}
+
+#[test]
+fn object_conversion() {
+    use std::convert::TryFrom;
+
+    let compiler = run("var a = 1; fun f() {}").unwrap();
+    assert_eq!(compiler.global("a"), Object::Digit(1.0));
+    let f = compiler.environment.borrow().values["f"].clone();
+    assert_eq!(
+        Object::try_from(f).unwrap_err(),
+        "expected a value but got <fn f>"
+    );
+
+    let object = Object::String("s".to_string());
+    let reference = Rc::new(UnionObject::Reference(&object));
+    assert_eq!(Object::try_from(reference), Ok(object.clone()));
+}
@@ -0,0 +1,324 @@
+// 仿照 rustc 的错误输出：
+//
+// error: expect expression
+//  --> main.lox:1:9
+//   |
+// 1 | var a = ;
+//   |         ^
+//   |
+//   = note: ...
+
+use std::fmt::Write;
+
+use super::{
+    error::{LexError, ParseError, RuntimeError, SemanticError},
+    json::Json,
+    token::Span,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => BOLD,
+        }
+    }
+}
+
+/// 输出格式：纯文本或带 ANSI 颜色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    Color,
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// 主要位置，使用 `^` 标出
+    pub primary: Label,
+    /// 次要位置，使用 `-` 标出
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new<T: Into<String>>(severity: Severity, message: T, span: Span) -> Self {
+        Diagnostic {
+            severity,
+            message: message.into(),
+            primary: Label {
+                span,
+                message: String::new(),
+            },
+            secondary: vec![],
+            notes: vec![],
+        }
+    }
+
+    pub fn error<T: Into<String>>(message: T, span: Span) -> Self {
+        Self::new(Severity::Error, message, span)
+    }
+
+    pub fn warning<T: Into<String>>(message: T, span: Span) -> Self {
+        Self::new(Severity::Warning, message, span)
+    }
+
+    /// 主要位置下方的说明文字
+    pub fn with_message<T: Into<String>>(mut self, message: T) -> Self {
+        self.primary.message = message.into();
+        self
+    }
+
+    pub fn with_label<T: Into<String>>(mut self, span: Span, message: T) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note<T: Into<String>>(mut self, note: T) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn render(&self, file: &str, source: &str, style: Style) -> String {
+        let paint = |color: &str, text: &str| match style {
+            Style::Plain => text.to_string(),
+            Style::Color => format!("{}{}{}", color, text, RESET),
+        };
+
+        let mut labels: Vec<(&Label, bool)> = vec![(&self.primary, true)];
+        labels.extend(self.secondary.iter().map(|l| (l, false)));
+        labels.sort_by_key(|(l, _)| (l.span.line, l.span.column));
+
+        let width = labels
+            .iter()
+            .map(|(l, _)| l.span.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let gutter = paint(BLUE, &format!("{} |", " ".repeat(width)));
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}{}",
+            paint(self.severity.color(), self.severity.name()),
+            paint(BOLD, &format!(": {}", self.message))
+        );
+        let _ = writeln!(
+            out,
+            "{}{}:{}:{}",
+            paint(BLUE, &format!("{}--> ", " ".repeat(width))),
+            file,
+            self.primary.span.line,
+            self.primary.span.column.max(1)
+        );
+        let _ = writeln!(out, "{}", gutter);
+
+        let mut current_line = 0;
+        for (label, primary) in labels {
+            let line = label.span.line;
+            let text = source.lines().nth(line.saturating_sub(1)).unwrap_or("");
+            if line != current_line {
+                current_line = line;
+                let _ = writeln!(
+                    out,
+                    "{} {}",
+                    paint(BLUE, &format!("{:>width$} |", line, width = width)),
+                    text
+                );
+            }
+
+            let (column, len) = underline(label.span, source, text);
+            let (marker, color) = if primary {
+                ("^", self.severity.color())
+            } else {
+                ("-", BLUE)
+            };
+            let mut mark = marker.repeat(len);
+            if !label.message.is_empty() {
+                mark = format!("{} {}", mark, label.message);
+            }
+            let _ = writeln!(
+                out,
+                "{} {}{}",
+                gutter,
+                " ".repeat(column - 1),
+                paint(color, &mark)
+            );
+        }
+
+        if !self.notes.is_empty() {
+            let _ = writeln!(out, "{}", gutter);
+        }
+        for note in &self.notes {
+            let _ = writeln!(
+                out,
+                "{} {} {}",
+                " ".repeat(width),
+                paint(BLUE, "="),
+                paint(BOLD, &format!("note: {}", note))
+            );
+        }
+
+        out
+    }
+
+    /// 给 CI 使用的 JSON 格式
+    pub fn to_json(&self, file: &str, source: &str) -> Json {
+        let location = |span: Span| {
+            let (end_line, end_column) = end_position(span, source);
+            vec![
+                ("line", Json::from(span.line)),
+                ("column", Json::from(span.column)),
+                ("end_line", Json::from(end_line)),
+                ("end_column", Json::from(end_column)),
+            ]
+        };
+
+        let mut labels = vec![(&self.primary, true)];
+        labels.extend(self.secondary.iter().map(|l| (l, false)));
+        let labels = labels
+            .into_iter()
+            .map(|(label, primary)| {
+                let mut fields = vec![
+                    ("message", Json::from(label.message.as_str())),
+                    ("primary", Json::from(primary)),
+                ];
+                fields.extend(location(label.span));
+                Json::object(fields)
+            })
+            .collect();
+
+        let mut fields = vec![
+            ("severity", Json::from(self.severity.name())),
+            ("message", Json::from(self.message.as_str())),
+            ("file", Json::from(file)),
+        ];
+        fields.extend(location(self.primary.span));
+        fields.push(("labels", Json::Array(labels)));
+        fields.push(("notes", Json::from(self.notes.clone())));
+        Json::object(fields)
+    }
+}
+
+/// 计算下划线的起始列和长度，跨行的 span 只标出第一行
+fn underline(span: Span, source: &str, text: &str) -> (usize, usize) {
+    let width = text.chars().count();
+    // 没有列信息时指向行尾
+    if span.column == 0 {
+        return (width + 1, 1);
+    }
+
+    let len = source
+        .get(span.offset..span.end())
+        .map_or(0, |s| s.chars().take_while(|c| *c != '\n').count());
+    let len = len.min((width + 1).saturating_sub(span.column));
+    (span.column, len.max(1))
+}
+
+/// span 结束位置的行列号（列号不包含）
+fn end_position(span: Span, source: &str) -> (usize, usize) {
+    match source.get(span.offset..span.end()) {
+        Some(text) if span.column > 0 => {
+            let lines = text.matches('\n').count();
+            let column = match text.rfind('\n') {
+                Some(i) => text[i + 1..].chars().count() + 1,
+                None => span.column + text.chars().count(),
+            };
+            (span.line + lines, column)
+        }
+        _ => (span.line, span.column),
+    }
+}
+
+impl From<&LexError> for Diagnostic {
+    fn from(error: &LexError) -> Self {
+        Diagnostic::error(error.message(), error.span)
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        Diagnostic::error(error.message.clone(), error.token.span)
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Self {
+        Diagnostic::error(error.message.clone(), error.token.span)
+    }
+}
+
+impl From<&SemanticError> for Diagnostic {
+    fn from(error: &SemanticError) -> Self {
+        Diagnostic::error(error.message.clone(), error.token.span)
+    }
+}
+
+#[test]
+fn render() {
+    use super::{lexer::Lexer, parser::Parser};
+
+    let source = "var a = 1;\nvar b = (a +;\n";
+    let mut l = Lexer::new(String::from(source));
+    let (tokens, _) = l.scan_tokens();
+    let (_, errors) = Parser::new(tokens.clone()).parse();
+
+    let diagnostic = Diagnostic::from(&errors[0])
+        .with_message("expected an operand here")
+        .with_label(tokens[8].span, "unclosed parenthesis")
+        .with_note("binary operators need two operands");
+
+    assert_eq!(
+        diagnostic.render("main.lox", source, Style::Plain),
+        "error: expect expression
+ --> main.lox:2:13
+  |
+2 | var b = (a +;
+  |         - unclosed parenthesis
+  |             ^ expected an operand here
+  |
+  = note: binary operators need two operands
+"
+    );
+
+    let colored = diagnostic.render("main.lox", source, Style::Color);
+    assert!(colored.contains("\x1b[1;31merror\x1b[0m"));
+
+    assert_eq!(
+        diagnostic.to_json("main.lox", source).to_string(),
+        r#"{"severity":"error","message":"expect expression","file":"main.lox","line":2,"column":13,"end_line":2,"end_column":14,"labels":[{"message":"expected an operand here","primary":true,"line":2,"column":13,"end_line":2,"end_column":14},{"message":"unclosed parenthesis","primary":false,"line":2,"column":9,"end_line":2,"end_column":10}],"notes":["binary operators need two operands"]}"#
+    );
+}
@@ -0,0 +1,346 @@
+// 用于调试语法分析器的 AST 输出。
+//
+// S 表达式与书中的 AstPrinter 相同，例如 `(* (- 123) (group 45.67))`；
+// JSON 包含节点类型、span 以及子节点，方便交给外部工具处理。
+
+use super::{
+    ast::{Expression, Statement},
+    diagnostic::Diagnostic,
+    json::Json,
+    lexer::Lexer,
+    parser::Parser,
+    printer::Printer,
+    token::{Object, Span},
+    visitor::{ExprVisitor, StmtVisitor},
+};
+
+/// 词法、语法分析，出错时返回全部诊断信息
+fn parse(source: &str) -> Result<Vec<Statement>, Vec<Diagnostic>> {
+    let (tokens, lex_errors) = Lexer::new(source.to_string()).scan_tokens();
+    let (statements, parse_errors) = Parser::new(tokens).parse();
+    let diagnostics: Vec<Diagnostic> = lex_errors
+        .iter()
+        .map(Diagnostic::from)
+        .chain(parse_errors.iter().map(Diagnostic::from))
+        .collect();
+    if diagnostics.is_empty() {
+        Ok(statements)
+    } else {
+        Err(diagnostics)
+    }
+}
+
+/// 源码的 S 表达式，每条顶层语句一行
+pub fn sexpr_source(source: &str) -> Result<String, Vec<Diagnostic>> {
+    parse(source).map(|statements| sexpr(&statements))
+}
+
+/// 源码的 JSON，不带末尾的换行
+pub fn json_source(source: &str) -> Result<String, Vec<Diagnostic>> {
+    parse(source).map(|statements| json(&statements).to_string())
+}
+
+/// 源码经过 AST 还原后的规范格式，for 循环按 while 输出
+pub fn lox_source(source: &str) -> Result<String, Vec<Diagnostic>> {
+    parse(source).map(|statements| Printer::new().print(&statements))
+}
+
+/// 每个 token 一行：行号、列号、类型以及原文，词法错误另外返回
+pub fn tokens(source: &str) -> (String, Vec<Diagnostic>) {
+    let (tokens, errors) = Lexer::new(source.to_string()).scan_tokens();
+    let mut out = String::new();
+    for token in &tokens {
+        let line = format!(
+            "{}:{} {:?} {}",
+            token.span.line, token.span.column, token.tag, token.lexeme
+        );
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    (out, errors.iter().map(Diagnostic::from).collect())
+}
+
+/// 每条顶层语句一行
+pub fn sexpr(statements: &[Statement]) -> String {
+    let mut printer = SExpr::default();
+    for stmt in statements {
+        printer.visit_stmt(stmt);
+        printer.out.push('\n');
+    }
+    printer.out
+}
+
+pub fn sexpr_expr(expr: &Expression) -> String {
+    let mut printer = SExpr::default();
+    printer.visit_expr(expr);
+    printer.out
+}
+
+#[derive(Default)]
+struct SExpr {
+    out: String,
+}
+
+impl SExpr {
+    /// 输出 `(name child child ...)`
+    fn parenthesize(&mut self, name: &str, exprs: &[&Expression], stmts: &[&Statement]) {
+        self.out.push('(');
+        self.out.push_str(name);
+        for expr in exprs {
+            self.out.push(' ');
+            self.visit_expr(expr);
+        }
+        for stmt in stmts {
+            self.out.push(' ');
+            self.visit_stmt(stmt);
+        }
+        self.out.push(')');
+    }
+}
+
+impl<'ast> StmtVisitor<'ast> for SExpr {
+    type StmtOutput = ();
+
+    fn visit_stmt(&mut self, stmt: &'ast Statement) {
+        match stmt {
+            Statement::Print(expr, _) => self.parenthesize("print", &[expr], &[]),
+            Statement::Expression(expr) => self.parenthesize("expr", &[expr], &[]),
+            Statement::Var(name, initializer, _) => {
+                let name = format!("var {}", name.lexeme);
+                let initializer: Vec<&Expression> = initializer.iter().collect();
+                self.parenthesize(&name, &initializer, &[]);
+            }
+            Statement::Block(statements, _) => {
+                let statements: Vec<&Statement> = statements.iter().collect();
+                self.parenthesize("block", &[], &statements);
+            }
+            Statement::If(_, condition, then_stmt, else_stmt) => {
+                let mut branches = vec![then_stmt.as_ref()];
+                branches.extend(else_stmt.as_deref());
+                self.parenthesize("if", &[condition], &branches);
+            }
+            Statement::Function(name, parameters, body, _) => {
+                let parameters: Vec<&str> = parameters.iter().map(|p| p.lexeme.as_str()).collect();
+                let name = format!("fun {} ({})", name.lexeme, parameters.join(" "));
+                let body: Vec<&Statement> = body.iter().collect();
+                self.parenthesize(&name, &[], &body);
+            }
+            Statement::Return(_, value) => {
+                let value: Vec<&Expression> = value.iter().collect();
+                self.parenthesize("return", &value, &[]);
+            }
+            Statement::While(_, condition, body) => {
+                self.parenthesize("while", &[condition], &[body])
+            }
+        }
+    }
+}
+
+impl<'ast> ExprVisitor<'ast> for SExpr {
+    type Output = ();
+
+    fn visit_expr(&mut self, expr: &'ast Expression) {
+        match expr {
+            Expression::Literal(Object::String(v), _) => {
+                self.out.push('"');
+                self.out.push_str(v);
+                self.out.push('"');
+            }
+            Expression::Literal(object, _) => self.out.push_str(&object.to_string()),
+            Expression::Assignment(name, value, _) => {
+                self.parenthesize(&format!("= {}", name.lexeme), &[value], &[])
+            }
+            Expression::Unary(operator, right) => {
+                self.parenthesize(&operator.lexeme, &[right], &[])
+            }
+            Expression::Binary(left, operator, right)
+            | Expression::Logical(left, operator, right) => {
+                self.parenthesize(&operator.lexeme, &[left, right], &[])
+            }
+            Expression::Grouping(inner, _) => self.parenthesize("group", &[inner], &[]),
+            Expression::Call(callee, _, arguments) => {
+                let mut exprs = vec![callee.as_ref()];
+                exprs.extend(arguments.iter());
+                self.parenthesize("call", &exprs, &[]);
+            }
+            Expression::Var(name, _) => self.out.push_str(&name.lexeme),
+            Expression::Mark => self.out.push_str("mark"),
+        }
+    }
+}
+
+/// 顶层语句组成的数组
+pub fn json(statements: &[Statement]) -> Json {
+    Json::Array(statements.iter().map(stmt_json).collect())
+}
+
+fn span_json(span: Span) -> Json {
+    Json::object(vec![
+        ("offset", Json::from(span.offset)),
+        ("len", Json::from(span.len)),
+        ("line", Json::from(span.line)),
+        ("column", Json::from(span.column)),
+    ])
+}
+
+/// 所有节点都有 kind、span 和 children，其余字段因节点而异
+fn node(kind: &str, span: Span, fields: Vec<(&str, Json)>, children: Vec<Json>) -> Json {
+    let mut all = vec![("kind", Json::from(kind)), ("span", span_json(span))];
+    all.extend(fields);
+    all.push(("children", Json::Array(children)));
+    Json::object(all)
+}
+
+fn object_json(object: &Object) -> Json {
+    match object {
+        Object::String(v) => Json::from(v.as_str()),
+        // 直接转换成 f64 会带出 f32 的误差，按最短的十进制表示转换
+        Object::Digit(v) => Json::from(v.to_string().parse::<f64>().unwrap_or(*v as f64)),
+        Object::Bool(v) => Json::from(*v),
+        Object::Nil | Object::Placeholder => Json::Null,
+    }
+}
+
+pub fn expr_json(expr: &Expression) -> Json {
+    let span = expr.span();
+    match expr {
+        Expression::Literal(object, _) => node(
+            "Literal",
+            span,
+            vec![("value", object_json(object))],
+            vec![],
+        ),
+        Expression::Assignment(name, value, _) => node(
+            "Assignment",
+            span,
+            vec![("name", Json::from(name.lexeme.as_str()))],
+            vec![expr_json(value)],
+        ),
+        Expression::Unary(operator, right) => node(
+            "Unary",
+            span,
+            vec![("operator", Json::from(operator.lexeme.as_str()))],
+            vec![expr_json(right)],
+        ),
+        Expression::Binary(left, operator, right) => node(
+            "Binary",
+            span,
+            vec![("operator", Json::from(operator.lexeme.as_str()))],
+            vec![expr_json(left), expr_json(right)],
+        ),
+        Expression::Logical(left, operator, right) => node(
+            "Logical",
+            span,
+            vec![("operator", Json::from(operator.lexeme.as_str()))],
+            vec![expr_json(left), expr_json(right)],
+        ),
+        Expression::Grouping(inner, _) => node("Grouping", span, vec![], vec![expr_json(inner)]),
+        Expression::Call(callee, _, arguments) => {
+            let mut children = vec![expr_json(callee)];
+            children.extend(arguments.iter().map(expr_json));
+            node("Call", span, vec![], children)
+        }
+        Expression::Var(name, _) => node(
+            "Var",
+            span,
+            vec![("name", Json::from(name.lexeme.as_str()))],
+            vec![],
+        ),
+        Expression::Mark => node("Mark", span, vec![], vec![]),
+    }
+}
+
+pub fn stmt_json(stmt: &Statement) -> Json {
+    let span = stmt.span();
+    match stmt {
+        Statement::Print(expr, _) => node("Print", span, vec![], vec![expr_json(expr)]),
+        Statement::Expression(expr) => node("Expression", span, vec![], vec![expr_json(expr)]),
+        Statement::Var(name, initializer, _) => node(
+            "Var",
+            span,
+            vec![("name", Json::from(name.lexeme.as_str()))],
+            initializer.iter().map(expr_json).collect(),
+        ),
+        Statement::Block(statements, _) => node(
+            "Block",
+            span,
+            vec![],
+            statements.iter().map(stmt_json).collect(),
+        ),
+        Statement::If(_, condition, then_stmt, else_stmt) => {
+            let mut children = vec![expr_json(condition), stmt_json(then_stmt)];
+            children.extend(else_stmt.iter().map(|s| stmt_json(s)));
+            node("If", span, vec![], children)
+        }
+        Statement::Function(name, parameters, body, _) => {
+            let parameters: Vec<&str> = parameters.iter().map(|p| p.lexeme.as_str()).collect();
+            node(
+                "Function",
+                span,
+                vec![
+                    ("name", Json::from(name.lexeme.as_str())),
+                    ("params", Json::from(parameters)),
+                ],
+                body.iter().map(stmt_json).collect(),
+            )
+        }
+        Statement::Return(_, value) => node(
+            "Return",
+            span,
+            vec![],
+            value.iter().map(expr_json).collect(),
+        ),
+        Statement::While(_, condition, body) => node(
+            "While",
+            span,
+            vec![],
+            vec![expr_json(condition), stmt_json(body)],
+        ),
+    }
+}
+
+#[test]
+fn dump() {
+    use super::{lexer::Lexer, parser::Parser};
+
+    let (tokens, _) = Lexer::new(String::from(
+        "print -123 * (45.67);
+        fun f(a, b) { return a or \"s\"; }
+        if (x) y = f(1, nil); else while (true) {}",
+    ))
+    .scan_tokens();
+    let (statements, _) = Parser::new(tokens).parse();
+
+    assert_eq!(
+        sexpr(&statements),
+        "(print (* (- 123) (group 45.67)))
+(fun f (a b) (return (or a \"s\")))
+(if x (expr (= y (call f 1 nil))) (while true (block)))
+"
+    );
+
+    assert_eq!(
+        stmt_json(&statements[0]).to_string(),
+        r#"{"kind":"Print","span":{"offset":0,"len":20,"line":1,"column":1},"children":[{"kind":"Binary","span":{"offset":6,"len":14,"line":1,"column":7},"operator":"*","children":[{"kind":"Unary","span":{"offset":6,"len":4,"line":1,"column":7},"operator":"-","children":[{"kind":"Literal","span":{"offset":7,"len":3,"line":1,"column":8},"value":123,"children":[]}]},{"kind":"Grouping","span":{"offset":13,"len":7,"line":1,"column":14},"children":[{"kind":"Literal","span":{"offset":14,"len":5,"line":1,"column":15},"value":45.67,"children":[]}]}]}]}"#
+    );
+    assert!(json(&statements).to_string().contains(
+        r#""kind":"Function","span":{"offset":30,"len":32,"line":2,"column":9},"name":"f","params":["a","b"]"#
+    ));
+}
+
+#[test]
+fn dump_source() {
+    assert_eq!(sexpr_source("print 1 + 2;").unwrap(), "(print (+ 1 2))\n");
+    assert!(json_source("nil;")
+        .unwrap()
+        .starts_with(r#"[{"kind":"Expression""#));
+    assert_eq!(sexpr_source("print (1;").unwrap_err().len(), 1);
+    assert_eq!(
+        lox_source("for(var i=0;i<1;i=i+1)print i;").unwrap(),
+        "{\n    var i = 0;\n    while (i < 1) {\n        print i;\n        i = i + 1;\n    }\n}\n"
+    );
+
+    let (out, errors) = tokens("var a = @;");
+    assert_eq!(out.lines().next(), Some("1:1 VAR var"));
+    assert_eq!(errors.len(), 1);
+}
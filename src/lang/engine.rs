@@ -0,0 +1,542 @@
+// 供其他 Rust 程序嵌入脚本的接口。
+//
+// Engine 隐藏了 Lexer、Parser、Resolver 和 Compiler，多次 eval 共用同一个全局环境；
+// 与脚本交换数据统一使用 Value。
+
+use std::{convert::TryFrom, fmt::Display, fs, io, path::Path, rc::Rc, time::Instant};
+
+use super::{
+    ast::Statement,
+    compiler::Compiler,
+    diagnostic::Diagnostic,
+    lexer::Lexer,
+    lint::{Level, LintConfig, Linter},
+    parser::Parser,
+    resolver::Resolver,
+    token::{Native, Object, UnionObject},
+};
+
+/// 脚本中的值
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    /// 与解释器一致，数字都是单精度浮点数
+    Number(f32),
+    String(String),
+    /// 脚本中定义的函数或者本地函数，可以取出来再通过 set_global 交给其他名字
+    Function(Function),
+}
+
+/// 函数的句柄，内部是 UnionObject::Function 或者 UnionObject::Native
+#[derive(Clone)]
+pub struct Function(UnionObject<'static>);
+
+impl Function {
+    pub fn name(&self) -> &str {
+        match &self.0 {
+            UnionObject::Function(closure) => closure.name(),
+            UnionObject::Native(native) => &native.name,
+            _ => "anonymous",
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        match &self.0 {
+            UnionObject::Function(closure) => match closure.declaration.as_ref() {
+                Statement::Function(_, parameters, ..) => parameters.len(),
+                _ => 0,
+            },
+            UnionObject::Native(native) => native.arity,
+            _ => 0,
+        }
+    }
+}
+
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// 同一个函数值才相等
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.equals(&other.0)
+    }
+}
+
+impl Value {
+    /// 只有 false 和 nil 为假，其余都为真
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    /// 类型名，用于参数类型错误的提示
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Function(_) => "function",
+        }
+    }
+
+    fn mismatch(&self, expected: &str) -> String {
+        format!("expected {} but got {}", expected, self.type_name())
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Number(v) => write!(f, "{}", v),
+            Value::String(v) => write!(f, "{}", v),
+            Value::Function(v) => write!(f, "{:?}", v),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Bool(v)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(v: f32) -> Self {
+        Value::Number(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Number(v as f32)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(v: i32) -> Self {
+        Value::Number(v as f32)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::String(v.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::String(v)
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Nil
+    }
+}
+
+impl TryFrom<&Value> for f32 {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Number(v) => Ok(*v),
+            other => Err(other.mismatch("number")),
+        }
+    }
+}
+
+impl TryFrom<&Value> for f64 {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        f32::try_from(value).map(f64::from)
+    }
+}
+
+impl TryFrom<&Value> for bool {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(v) => Ok(*v),
+            other => Err(other.mismatch("boolean")),
+        }
+    }
+}
+
+impl TryFrom<&Value> for String {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(v) => Ok(v.clone()),
+            other => Err(other.mismatch("string")),
+        }
+    }
+}
+
+impl From<&UnionObject<'static>> for Value {
+    fn from(object: &UnionObject<'static>) -> Self {
+        let object = match object {
+            UnionObject::Value(v) => v,
+            UnionObject::Reference(v) => *v,
+            UnionObject::Function(_) | UnionObject::Native(_) => {
+                return Value::Function(Function(object.clone()))
+            }
+        };
+        match object {
+            Object::String(v) => Value::String(v.clone()),
+            Object::Digit(v) => Value::Number(*v),
+            Object::Bool(v) => Value::Bool(*v),
+            Object::Nil | Object::Placeholder => Value::Nil,
+        }
+    }
+}
+
+impl From<Value> for UnionObject<'static> {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Nil => UnionObject::Value(Object::Nil),
+            Value::Bool(v) => UnionObject::Value(Object::Bool(v)),
+            Value::Number(v) => UnionObject::Value(Object::Digit(v)),
+            Value::String(v) => UnionObject::Value(Object::String(v)),
+            Value::Function(Function(function)) => function,
+        }
+    }
+}
+
+/// eval 和 run_file 的错误
+#[derive(Debug)]
+pub enum Error {
+    /// 读取脚本文件失败
+    Io(io::Error),
+    /// 词法、语法、静态检查错误或者 deny 级别的 lint，脚本没有执行
+    Compile(Vec<Diagnostic>),
+    /// 执行过程中出错，出错之前的语句已经生效
+    Runtime(Diagnostic),
+}
+
+impl Error {
+    /// 可以用 Diagnostic::render 配合源码输出
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            Error::Io(_) => &[],
+            Error::Compile(diagnostics) => diagnostics,
+            Error::Runtime(diagnostic) => std::slice::from_ref(diagnostic),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Error::Io(e) = self {
+            return write!(f, "{}", e);
+        }
+        let messages: Vec<String> = self
+            .diagnostics()
+            .iter()
+            .map(|d| format!("[line {}] Error: {}", d.primary.span.line, d.message))
+            .collect();
+        write!(f, "{}", messages.join("\n"))
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// 脚本引擎，保存全局变量和已经定义的函数
+pub struct Engine {
+    compiler: Compiler<'static>,
+    resolver: Resolver,
+    /// 设置之后每次执行前都做代码检查
+    lints: Option<LintConfig>,
+    warnings: Vec<Diagnostic>,
+}
+
+impl Engine {
+    /// 预先定义了内置函数 clock()。
+    /// 与书中不同，clock() 返回的是从创建 Engine 开始经过的秒数，而不是从 1970 年开始：
+    /// 数字是 f32，距 1970 年的秒数精度不够
+    pub fn new() -> Self {
+        let mut engine = Engine {
+            compiler: Compiler::new(),
+            resolver: Resolver::new(),
+            lints: None,
+            warnings: vec![],
+        };
+        let start = Instant::now();
+        engine.register_fn("clock", 0, move |_| Ok(start.elapsed().as_secs_f32()));
+        engine
+    }
+
+    /// 执行前先做代码检查，源码中的 `// lint:` 注释会覆盖 config 中的级别。
+    /// deny 级别的结果按编译错误返回，脚本不会执行；其余的结果通过 take_warnings 取出
+    pub fn with_lints(mut self, config: LintConfig) -> Self {
+        self.lints = Some(config);
+        self
+    }
+
+    /// 取出之前执行时产生的警告
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// 注册本地函数，调用时参数个数已经检查过。
+    /// 返回的错误信息作为运行时错误报告，参数可以用 TryFrom 转换并检查类型
+    pub fn register_fn<F, R>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[Value]) -> Result<R, String> + 'static,
+        R: Into<Value>,
+    {
+        let native = Native {
+            name: name.to_string(),
+            arity,
+            function: Rc::new(move |args: &[Rc<UnionObject<'static>>]| {
+                let args: Vec<Value> = args.iter().map(|arg| Value::from(arg.as_ref())).collect();
+                let result = function(&args)?.into();
+                Ok(Rc::new(UnionObject::from(result)))
+            }),
+        };
+        self.resolver.declare_global(name, Some(arity));
+        self.compiler
+            .define_global(name, Rc::new(UnionObject::Native(native)));
+    }
+
+    /// 执行一段脚本，返回最后一条表达式语句的值，没有表达式语句时返回 nil
+    pub fn eval(&mut self, source: &str) -> Result<Value, Error> {
+        let values = self.execute(source)?;
+        Ok(values
+            .last()
+            .map_or(Value::Nil, |v| Value::from(v.as_ref())))
+    }
+
+    pub fn run_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Value, Error> {
+        let source = fs::read_to_string(path)?;
+        self.eval(&source)
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.compiler
+            .get_global(name)
+            .map(|value| Value::from(value.as_ref()))
+    }
+
+    /// 定义或者覆盖全局变量，之后的脚本可以直接使用
+    pub fn set_global<T: Into<Value>>(&mut self, name: &str, value: T) {
+        let value = value.into();
+        let arity = match &value {
+            Value::Function(function) => Some(function.arity()),
+            _ => None,
+        };
+        self.resolver.declare_global(name, arity);
+        self.compiler.define_global(name, Rc::new(value.into()));
+    }
+
+    /// 全部全局变量，按名字排序
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        self.compiler
+            .globals()
+            .into_iter()
+            .map(|(name, value)| (name, Value::from(value.as_ref())))
+            .collect()
+    }
+
+    /// 执行脚本，返回每条顶层表达式语句的值
+    pub(crate) fn execute(&mut self, source: &str) -> Result<Vec<Rc<UnionObject<'static>>>, Error> {
+        let (tokens, lex_errors) = Lexer::new(source.to_string()).scan_tokens();
+        let (mut statements, parse_errors) = Parser::new(tokens).parse();
+        let diagnostics: Vec<Diagnostic> = lex_errors
+            .iter()
+            .map(Diagnostic::from)
+            .chain(parse_errors.iter().map(Diagnostic::from))
+            .collect();
+        if !diagnostics.is_empty() {
+            return Err(Error::Compile(diagnostics));
+        }
+
+        // 有静态错误时恢复 Resolver，本次脚本中的声明不算数
+        let snapshot = self.resolver.clone();
+        let errors = self.resolver.resolve(&mut statements);
+        if !errors.is_empty() {
+            self.resolver = snapshot;
+            return Err(Error::Compile(
+                errors.iter().map(Diagnostic::from).collect(),
+            ));
+        }
+
+        if let Some(config) = &self.lints {
+            let mut config = config.clone();
+            config.apply_source(source);
+            let (denied, warnings): (Vec<_>, Vec<_>) = Linter::new(config)
+                .lint(&statements)
+                .into_iter()
+                .partition(|message| message.level == Level::Deny);
+            self.warnings.extend(warnings.iter().map(Diagnostic::from));
+            if !denied.is_empty() {
+                self.resolver = snapshot;
+                return Err(Error::Compile(
+                    denied.iter().map(Diagnostic::from).collect(),
+                ));
+            }
+        }
+
+        let mut values = vec![];
+        for stmt in statements {
+            let result = match stmt {
+                Statement::Expression(expr) => self
+                    .compiler
+                    .evaluate(&expr)
+                    .map(|value| values.push(value)),
+                stmt => self.compiler.interpret(vec![stmt]),
+            };
+            result.map_err(|error| Error::Runtime(Diagnostic::from(&error)))?;
+        }
+        Ok(values)
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn embed() {
+    let mut engine = Engine::new();
+    assert_eq!(engine.eval("1 + 2").unwrap(), Value::Number(3.0));
+    assert_eq!(engine.eval("var a = \"x\";").unwrap(), Value::Nil);
+
+    engine.set_global("b", 2);
+    engine.set_global("name", "lox");
+    assert_eq!(
+        engine.eval("a + name; b * 10").unwrap(),
+        Value::Number(20.0)
+    );
+    assert_eq!(engine.get_global("a"), Some(Value::from("x")));
+    assert_eq!(engine.get_global("missing"), None);
+
+    engine.eval("fun twice(n) { return n * 2; }").unwrap();
+    let twice = engine.get_global("twice").unwrap();
+    match &twice {
+        Value::Function(function) => assert_eq!((function.name(), function.arity()), ("twice", 1)),
+        other => panic!("{:?}", other),
+    }
+    engine.set_global("double", twice);
+    assert_eq!(engine.eval("double(b)").unwrap(), Value::Number(4.0));
+    assert!(matches!(
+        engine.eval("double(1, 2)"),
+        Err(Error::Compile(_))
+    ));
+}
+
+#[test]
+fn native() {
+    let mut engine = Engine::new();
+    engine.register_fn("repeat", 2, |args| {
+        let text = String::try_from(&args[0])?;
+        let count = f32::try_from(&args[1])?;
+        Ok(text.repeat(count as usize))
+    });
+    assert_eq!(
+        engine.eval("repeat(\"ab\", 3)").unwrap(),
+        Value::from("ababab")
+    );
+
+    let error = engine.eval("repeat(1, 2)").unwrap_err();
+    assert!(matches!(error, Error::Runtime(_)), "{:?}", error);
+    assert_eq!(
+        error.to_string(),
+        "[line 1] Error: expected string but got number"
+    );
+    // 参数个数在执行之前就能检查出来
+    assert!(matches!(
+        engine.eval("repeat(\"a\")"),
+        Err(Error::Compile(_))
+    ));
+    assert!(matches!(
+        engine.eval("var f = repeat; f(\"a\")"),
+        Err(Error::Runtime(_))
+    ));
+
+    let start = engine.eval("clock()").unwrap();
+    assert!(matches!(start, Value::Number(t) if t >= 0.0));
+    assert_eq!(
+        engine.eval("clock").unwrap().to_string(),
+        "<native fn clock>"
+    );
+    match engine.get_global("repeat") {
+        Some(Value::Function(f)) => assert_eq!((f.name(), f.arity()), ("repeat", 2)),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn errors() {
+    let mut engine = Engine::new();
+    let error = engine.eval("var a = 1;\nvar b = ;").unwrap_err();
+    assert_eq!(error.to_string(), "[line 2] Error: expect expression");
+    assert_eq!(engine.get_global("a"), None);
+
+    let error = engine
+        .eval("var a = 1;\nprint a + nil;\nvar c = 3;")
+        .unwrap_err();
+    assert!(matches!(error, Error::Runtime(_)), "{:?}", error);
+    assert_eq!(engine.get_global("a"), Some(Value::Number(1.0)));
+    assert_eq!(engine.get_global("c"), None);
+
+    assert!(matches!(engine.run_file("missing.lox"), Err(Error::Io(_))));
+}
+
+#[test]
+fn lints() {
+    use super::lint::Lint;
+
+    let source = "{ var unused = 1; }\nvar ran = true;";
+    let mut engine = Engine::new().with_lints(LintConfig::new());
+    engine.eval(source).unwrap();
+    let warnings = engine.take_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].message, "unused variable 'unused'");
+    assert!(engine.take_warnings().is_empty());
+
+    // deny 的结果按编译错误返回，脚本没有执行
+    let mut config = LintConfig::new();
+    config.set(Lint::UnusedVariable, Level::Deny);
+    let mut engine = Engine::new().with_lints(config);
+    let error = engine.eval(source).unwrap_err();
+    assert!(matches!(error, Error::Compile(_)), "{:?}", error);
+    assert_eq!(engine.get_global("ran"), None);
+
+    // 源码中的注释同样生效
+    let mut engine = Engine::new().with_lints(LintConfig::new());
+    let source = "// lint: deny(self_assignment)\nvar a = 1;\na = a;";
+    assert!(matches!(engine.eval(source), Err(Error::Compile(_))));
+    assert_eq!(engine.get_global("a"), None);
+    assert!(engine.eval("var a = 1;").is_ok());
+
+    // 没有设置时不做检查
+    let mut engine = Engine::new();
+    engine.eval(source).unwrap();
+    assert!(engine.take_warnings().is_empty());
+}
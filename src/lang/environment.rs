@@ -1,6 +1,9 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use super::token::UnionObject;
+use super::{
+    error::RuntimeError,
+    token::{Token, UnionObject},
+};
 
 #[derive(Debug)]
 pub struct Environment<'a> {
@@ -20,26 +23,83 @@ impl<'a> Environment<'a> {
         self.values.insert(name, value);
     }
 
-    pub fn assign(&mut self, name: String, value: Rc<UnionObject<'a>>) {
+    /// 赋值给最近一个声明了该变量的作用域
+    pub fn assign(&mut self, name: &Token, value: Rc<UnionObject<'a>>) -> Result<(), RuntimeError> {
+        if self.values.contains_key(&name.lexeme) {
+            self.values.insert(name.lexeme.clone(), value);
+            return Ok(());
+        }
+
         if let Some(e) = &self.enclosing {
             return e.borrow_mut().assign(name, value);
         }
 
-        if self.values.contains_key(&name) {
-            self.values.insert(name, value);
-        }
-        // TODO: 返回赋值
-        // *value
+        Err(undefined(name))
     }
 
-    pub fn retrieve(&self, name: String) -> Rc<UnionObject<'a>> {
-        if let Some(object) = self.values.get(&name) {
-            object.clone()
+    pub fn retrieve(&self, name: &Token) -> Result<Rc<UnionObject<'a>>, RuntimeError> {
+        if let Some(object) = self.values.get(&name.lexeme) {
+            Ok(object.clone())
         } else if let Some(e) = &self.enclosing {
             // 作用域查找
-            return e.borrow_mut().retrieve(name);
+            e.borrow().retrieve(name)
         } else {
-            panic!("undefined variable")
+            Err(undefined(name))
+        }
+    }
+}
+
+/// 沿着外层作用域向上 distance 层
+pub fn ancestor<'a>(
+    environment: &Rc<RefCell<Environment<'a>>>,
+    distance: usize,
+) -> Rc<RefCell<Environment<'a>>> {
+    let mut environment = environment.clone();
+    for _ in 0..distance {
+        let enclosing = environment
+            .borrow()
+            .enclosing
+            .clone()
+            .expect("resolved scope depth is deeper than the environment chain");
+        environment = enclosing;
+    }
+    environment
+}
+
+/// 在 Resolver 给出的作用域中读取变量，不再逐层按名字查找
+pub fn get_at<'a>(
+    environment: &Rc<RefCell<Environment<'a>>>,
+    distance: usize,
+    name: &Token,
+) -> Result<Rc<UnionObject<'a>>, RuntimeError> {
+    ancestor(environment, distance)
+        .borrow()
+        .values
+        .get(&name.lexeme)
+        .cloned()
+        .ok_or_else(|| undefined(name))
+}
+
+pub fn assign_at<'a>(
+    environment: &Rc<RefCell<Environment<'a>>>,
+    distance: usize,
+    name: &Token,
+    value: Rc<UnionObject<'a>>,
+) -> Result<(), RuntimeError> {
+    let target = ancestor(environment, distance);
+    let mut target = target.borrow_mut();
+    match target.values.get_mut(&name.lexeme) {
+        Some(slot) => {
+            *slot = value;
+            Ok(())
         }
+        None => Err(undefined(name)),
     }
 }
+
+fn undefined(name: &Token) -> RuntimeError {
+    RuntimeError::new(
+        name.clone(),
+        format!("Undefined variable '{}'", name.lexeme),
+    )
+}
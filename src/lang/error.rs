@@ -0,0 +1,121 @@
+use std::fmt::Display;
+
+use super::token::{Span, Token, TokenType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexErrorKind {
+    UnexpectedCharacter(char),
+    UnterminatedString,
+    MalformedNumber(String),
+}
+
+/// 词法分析错误，span 覆盖出错的字符
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+impl LexError {
+    pub fn message(&self) -> String {
+        match &self.kind {
+            LexErrorKind::UnexpectedCharacter(c) => format!("unexpected character '{}'", c),
+            LexErrorKind::UnterminatedString => "unterminated string".to_string(),
+            LexErrorKind::MalformedNumber(text) => format!("malformed number '{}'", text),
+        }
+    }
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] Error: {}", self.span.line, self.message())
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// 语法分析错误，记录出错的 token 以及所在行
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub token: Token,
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new<T: AsRef<str> + Display>(token: Token, message: T) -> Self {
+        ParseError {
+            line: token.line,
+            token,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.token.tag == TokenType::EOF {
+            write!(f, "[line {}] Error at end: {}", self.line, self.message)
+        } else {
+            write!(
+                f,
+                "[line {}] Error at '{}': {}",
+                self.line, self.token.lexeme, self.message
+            )
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// 运行时错误，记录导致错误的 token 以便定位
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub token: Token,
+    pub message: String,
+}
+
+impl RuntimeError {
+    pub fn new<T: AsRef<str> + Display>(token: Token, message: T) -> Self {
+        RuntimeError {
+            token,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] {}", self.token.line, self.message)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// 执行之前的静态检查发现的错误
+#[derive(Debug, Clone, PartialEq)]
+pub struct SemanticError {
+    pub token: Token,
+    pub message: String,
+}
+
+impl SemanticError {
+    pub fn new<T: AsRef<str> + Display>(token: Token, message: T) -> Self {
+        SemanticError {
+            token,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for SemanticError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[line {}] Error at '{}': {}",
+            self.token.line, self.token.lexeme, self.message
+        )
+    }
+}
+
+impl std::error::Error for SemanticError {}
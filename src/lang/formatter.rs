@@ -0,0 +1,480 @@
+// 基于 lossless token 的源码格式化。
+//
+// 与 Printer 不同，这里不经过 AST：注释、空行以及 for 循环都原样保留，
+// 只调整缩进、花括号位置、token 之间的空格，并拆分过长的行。
+// 源码中的换行会保留下来（分号可以省略，换行可能是语句的分隔），
+// 连续的多个空行合并为一个。
+
+use super::{
+    diagnostic::Diagnostic,
+    lexer::Lexer,
+    parser::Parser,
+    token::{Token, TokenType, TokenType::*, Trivia},
+};
+
+pub struct Formatter {
+    indent: String,
+    max_width: usize,
+}
+
+impl Formatter {
+    /// 默认 4 个空格缩进，每行最多 80 列
+    pub fn new() -> Self {
+        Formatter {
+            indent: "    ".to_string(),
+            max_width: 80,
+        }
+    }
+
+    pub fn with_indent<T: Into<String>>(mut self, indent: T) -> Self {
+        self.indent = indent.into();
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: usize) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    /// 有词法或语法错误时不做格式化
+    pub fn format(&self, source: &str) -> Result<String, Vec<Diagnostic>> {
+        let (tokens, errors) = Lexer::new(source.to_string()).lossless().scan_tokens();
+        if !errors.is_empty() {
+            return Err(errors.iter().map(Diagnostic::from).collect());
+        }
+        let (_, errors) = Parser::new(tokens.clone()).parse();
+        if !errors.is_empty() {
+            return Err(errors.iter().map(Diagnostic::from).collect());
+        }
+
+        let mut layout = Layout::default();
+        for (i, token) in tokens.iter().enumerate() {
+            layout.token(token, tokens.get(i + 1).map(|t| t.tag));
+        }
+
+        let mut out = String::new();
+        for line in &layout.lines {
+            if line.blank {
+                out.push('\n');
+            }
+            self.render(line, &mut out);
+        }
+        Ok(out)
+    }
+
+    /// 输出一行，超出宽度时在逗号之后或二元运算符之前换行
+    fn render(&self, line: &Line, out: &mut String) {
+        let indent = |level: usize| self.indent.repeat(level);
+        let mut text = indent(line.indent);
+        // 位于行首，前面不需要空格
+        let mut fresh = true;
+        // 当前行中最后一个可以换行的位置
+        let mut last_break = None;
+
+        for part in &line.parts {
+            let len = (part.space && !fresh) as usize + part.text.chars().count();
+            if !fresh && text.chars().count() + len > self.max_width {
+                let at = if part.breakable {
+                    Some(text.len())
+                } else {
+                    last_break
+                };
+                if let Some(at) = at {
+                    let rest = text.split_off(at);
+                    out.push_str(text.trim_end());
+                    out.push('\n');
+                    text = indent(line.indent + 1) + rest.trim_start();
+                    fresh = rest.trim().is_empty();
+                    last_break = None;
+                }
+            }
+
+            if part.breakable && !fresh {
+                last_break = Some(text.len());
+            }
+            if part.space && !fresh {
+                text.push(' ');
+            }
+            text.push_str(&part.text);
+            fresh = false;
+        }
+
+        if let Some(comment) = &line.comment {
+            if !line.parts.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(comment);
+        }
+        out.push_str(text.trim_end());
+        out.push('\n');
+    }
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Part {
+    text: String,
+    tag: TokenType,
+    /// 与前一个 token 之间是否有空格
+    space: bool,
+    unary: bool,
+    /// 行太长时可以在它之前换行
+    breakable: bool,
+}
+
+#[derive(Default)]
+struct Line {
+    indent: usize,
+    parts: Vec<Part>,
+    /// 行尾注释，或者独占一行的注释
+    comment: Option<String>,
+    /// 之前是否有空行
+    blank: bool,
+}
+
+impl Line {
+    fn is_empty(&self) -> bool {
+        self.parts.is_empty() && self.comment.is_none()
+    }
+
+    /// 最后一个 token，有行尾注释时无法接上后面的内容
+    fn last_tag(&self) -> Option<TokenType> {
+        match self.comment {
+            Some(_) => None,
+            None => self.parts.last().map(|p| p.tag),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Layout {
+    lines: Vec<Line>,
+    current: Line,
+    /// 上一个 token
+    prev: Option<TokenType>,
+    /// 上一个 token 是否是 if / while / for 条件的右括号
+    header: bool,
+    /// 每层花括号所在行的缩进
+    braces: Vec<usize>,
+    /// 每层圆括号是否属于 if / while / for
+    parens: Vec<bool>,
+    /// 还可能接 else 的 if 所在行的缩进以及所在的花括号层数
+    ifs: Vec<(usize, usize)>,
+    /// 当前语句第一行的缩进，续行在此基础上缩进一层
+    statement: usize,
+    /// 下一个 token 需要另起一行
+    pending_break: bool,
+    /// 自上一段内容之后的换行数
+    newlines: usize,
+    blank: bool,
+}
+
+impl Layout {
+    fn token(&mut self, token: &Token, next: Option<TokenType>) {
+        for trivia in &token.trivia {
+            match trivia {
+                Trivia::Newline => {
+                    self.flush();
+                    self.newlines += 1;
+                    if self.newlines >= 2 {
+                        self.blank = true;
+                    }
+                }
+                Trivia::Comment(text) if !self.current.is_empty() && self.newlines == 0 => {
+                    self.current.comment = Some(text.clone());
+                    self.pending_break = true;
+                }
+                Trivia::Comment(text) => {
+                    self.flush();
+                    // 位于 } 之前的注释仍然属于块的内部
+                    let indent = self.block_indent();
+                    self.start_line(indent, None);
+                    self.current.comment = Some(text.clone());
+                    self.flush();
+                    self.newlines = 0;
+                }
+                Trivia::Whitespace(_) => {}
+            }
+        }
+
+        if token.tag == EOF {
+            self.flush();
+            return;
+        }
+        if self.pending_break || (token.tag == RIGHT_BRACE && self.prev != Some(LEFT_BRACE)) {
+            self.flush();
+        }
+        self.pending_break = false;
+
+        if self.current.is_empty() {
+            self.begin(token.tag);
+        }
+        self.push(token);
+
+        match token.tag {
+            LEFT_PAREN => self
+                .parens
+                .push(matches!(self.prev, Some(IF) | Some(WHILE) | Some(FOR))),
+            RIGHT_PAREN => {
+                let header = self.parens.pop().unwrap_or(false);
+                self.header = header && self.parens.is_empty();
+            }
+            // 块的缩进以语句的第一行为准，不受换行的参数列表影响
+            LEFT_BRACE => {
+                self.braces.push(self.statement);
+                self.pending_break = next != Some(RIGHT_BRACE);
+            }
+            RIGHT_BRACE => {
+                self.braces.pop();
+                let depth = self.braces.len();
+                self.ifs.retain(|&(_, d)| d <= depth);
+                self.pending_break = !matches!(
+                    next,
+                    Some(ELSE) | Some(SEMICOLON) | Some(RIGHT_PAREN) | Some(COMMA)
+                );
+            }
+            SEMICOLON => self.pending_break = self.parens.is_empty(),
+            IF => self.ifs.push((self.current.indent, self.braces.len())),
+            _ => {}
+        }
+        if token.tag != RIGHT_PAREN {
+            self.header = false;
+        }
+        self.prev = Some(token.tag);
+        self.newlines = 0;
+    }
+
+    /// 决定新一行的缩进，或者接到上一行后面
+    fn begin(&mut self, tag: TokenType) {
+        let last = self.lines.last().and_then(Line::last_tag);
+        let join = match tag {
+            LEFT_BRACE => matches!(last, Some(RIGHT_PAREN) | Some(ELSE)),
+            ELSE => last == Some(RIGHT_BRACE),
+            _ => false,
+        };
+        if join && !self.blank {
+            self.current = self.lines.pop().unwrap_or_default();
+            return;
+        }
+
+        let indent = if tag == RIGHT_BRACE {
+            self.braces.last().copied().unwrap_or(0)
+        } else if tag == ELSE {
+            // else 属于同一层花括号中最近的 if
+            self.else_indent()
+        } else if self.header || self.prev == Some(ELSE) {
+            // if / while / else 的主体不是块时缩进一层
+            self.lines.last().map_or(0, |l| l.indent) + 1
+        } else if self.continuation(tag) {
+            self.statement + 1
+        } else {
+            self.block_indent()
+        };
+        if !self.continuation(tag) {
+            self.statement = indent;
+        }
+        self.start_line(indent, Some(tag));
+    }
+
+    /// 新的一行是否是上一条语句的延续
+    fn continuation(&self, tag: TokenType) -> bool {
+        let prev = match self.prev {
+            Some(SEMICOLON) | Some(LEFT_BRACE) | Some(RIGHT_BRACE) | None => return false,
+            Some(prev) => prev,
+        };
+        if self.header || prev == ELSE {
+            return false;
+        }
+        !self.parens.is_empty()
+            || binary(prev)
+            || binary(tag)
+            || matches!(
+                prev,
+                EQUAL | COMMA | LEFT_PAREN | BANG | DOT | PRINT | VAR | RETURN | FUN | FUNCTION
+            )
+    }
+
+    fn else_indent(&mut self) -> usize {
+        let depth = self.braces.len();
+        match self.ifs.iter().rposition(|&(_, d)| d == depth) {
+            Some(i) => self.ifs.remove(i).0,
+            None => self.block_indent(),
+        }
+    }
+
+    fn block_indent(&self) -> usize {
+        self.braces.last().map_or(0, |i| i + 1)
+    }
+
+    fn start_line(&mut self, indent: usize, tag: Option<TokenType>) {
+        // 文件开头、块的开头和结尾不保留空行
+        let blank = std::mem::take(&mut self.blank)
+            && !self.lines.is_empty()
+            && tag != Some(RIGHT_BRACE)
+            && self.lines.last().and_then(Line::last_tag) != Some(LEFT_BRACE);
+        self.current = Line {
+            indent,
+            blank,
+            ..Default::default()
+        };
+    }
+
+    fn push(&mut self, token: &Token) {
+        let prev = self.current.parts.last();
+        // 行首的运算符要看上一行的结尾
+        let unary = matches!(token.tag, MINUS | BANG)
+            && !matches!(
+                self.prev,
+                Some(IDENTIFIER)
+                    | Some(NUMBER)
+                    | Some(STRING)
+                    | Some(TRUE)
+                    | Some(FALSE)
+                    | Some(NIL)
+                    | Some(RIGHT_PAREN)
+            );
+        let space = match prev {
+            None => false,
+            Some(prev) if prev.unary || matches!(prev.tag, LEFT_PAREN | DOT) => false,
+            Some(prev) => match token.tag {
+                RIGHT_PAREN | COMMA | SEMICOLON | DOT => false,
+                LEFT_PAREN => !matches!(prev.tag, IDENTIFIER | RIGHT_PAREN),
+                RIGHT_BRACE => prev.tag != LEFT_BRACE,
+                _ => true,
+            },
+        };
+        let breakable = match prev {
+            Some(prev) => prev.tag == COMMA || (binary(token.tag) && !unary),
+            None => false,
+        };
+        self.current.parts.push(Part {
+            text: token.lexeme.clone(),
+            tag: token.tag,
+            space,
+            unary,
+            breakable,
+        });
+    }
+
+    fn flush(&mut self) {
+        if !self.current.is_empty() {
+            let line = std::mem::take(&mut self.current);
+            self.lines.push(line);
+        }
+    }
+}
+
+/// 可以出现在两个操作数之间的运算符
+fn binary(tag: TokenType) -> bool {
+    matches!(
+        tag,
+        PLUS | MINUS
+            | STAR
+            | SLASH
+            | EQUAL_EQUAL
+            | BANG_EQUAL
+            | GREATER
+            | GREATER_EQUAL
+            | LESS
+            | LESS_EQUAL
+            | AND
+            | OR
+    )
+}
+
+#[test]
+fn format_source() {
+    let source = "// header
+
+
+var a=1+-2 ;var b = !a   // trailing
+fun add(a,b)
+{
+  return a+b;
+
+
+}
+if(a==b){print add(a,b);}
+else
+print\"no\";
+for (var i=0;i<3;i=i+1) print i;
+while (a) if (b)
+print a;
+{}
+";
+
+    let formatted = Formatter::new().format(source).unwrap();
+    assert_eq!(
+        formatted,
+        "// header
+
+var a = 1 + -2;
+var b = !a // trailing
+fun add(a, b) {
+    return a + b;
+}
+if (a == b) {
+    print add(a, b);
+} else
+    print \"no\";
+for (var i = 0; i < 3; i = i + 1) print i;
+while (a) if (b)
+    print a;
+{}
+"
+    );
+    // 格式化的结果不再变化
+    assert_eq!(Formatter::new().format(&formatted).unwrap(), formatted);
+}
+
+#[test]
+fn line_width() {
+    let source = "fun f() {
+\tprint call(firstArgument, secondArgument) + anotherValue * yetAnother - 1;
+}
+";
+    let formatter = Formatter::new().with_indent("\t").with_max_width(40);
+    let formatted = formatter.format(source).unwrap();
+    assert_eq!(
+        formatted,
+        "fun f() {
+\tprint call(firstArgument,
+\t\tsecondArgument) + anotherValue
+\t\t* yetAnother - 1;
+}
+"
+    );
+    assert_eq!(formatter.format(&formatted).unwrap(), formatted);
+
+    assert!(Formatter::new().format("var a = ;").is_err());
+}
+
+#[test]
+fn fixtures() {
+    let cases = [
+        // 换行的参数列表不影响函数体的缩进
+        (
+            "fun f(a,\nb) {\nreturn a;\n}\n",
+            "fun f(a,\n    b) {\n    return a;\n}\n",
+        ),
+        // } 之前的注释和块内的语句对齐
+        (
+            "{\nprint 1;\n// end\n}\n",
+            "{\n    print 1;\n    // end\n}\n",
+        ),
+        // else 和它所属的 if 对齐
+        (
+            "if (a)\nif (b)\nprint 1;\nelse\nprint 2;\nelse print 3;\n",
+            "if (a)\n    if (b)\n        print 1;\n    else\n        print 2;\nelse print 3;\n",
+        ),
+    ];
+    for (source, expected) in &cases {
+        let formatted = Formatter::new().format(source).unwrap();
+        assert_eq!(&formatted, expected);
+        assert_eq!(Formatter::new().format(&formatted).unwrap(), formatted);
+    }
+}
@@ -1,34 +1,94 @@
 use crate::lang::token::Keywords;
 
-use super::token::{Object, Token, TokenType};
+use super::{
+    error::{LexError, LexErrorKind},
+    token::{Object, Span, Token, TokenType, Trivia},
+};
 use TokenType::*;
 
 pub struct Lexer {
     pub source: Vec<char>,
     pub tokens: Vec<Token>,
+    pub errors: Vec<LexError>,
 
+    /// 每个字符在原始字符串中的字节偏移，最后一项为总长度
+    offsets: Vec<usize>,
     start: usize,
     current: usize,
     line: usize,
+    /// 当前行第一个字符的索引，用于计算列号
+    line_start: usize,
+    start_line: usize,
+    start_column: usize,
+    /// 是否保留空白和注释
+    lossless: bool,
+    /// 尚未附加到 token 上的 trivia
+    trivia: Vec<Trivia>,
 }
 
 #[allow(dead_code)]
 impl Lexer {
     pub fn new(input: String) -> Self {
+        let mut offsets: Vec<usize> = input.char_indices().map(|(i, _)| i).collect();
+        offsets.push(input.len());
+
         Lexer {
             source: input.chars().collect(),
             tokens: vec![],
+            errors: vec![],
+            offsets,
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_line: 1,
+            start_column: 1,
+            lossless: false,
+            trivia: vec![],
         }
     }
 
-    pub fn scan_tokens(&mut self) {
+    /// 保留空白、换行和注释，附加在随后的 token 上，供格式化工具使用
+    pub fn lossless(mut self) -> Self {
+        self.lossless = true;
+        self
+    }
+
+    /// 返回全部 token（总是以 EOF 结尾），以及扫描过程中遇到的错误
+    pub fn scan_tokens(&mut self) -> (Vec<Token>, Vec<LexError>) {
         while !self.is_at_end() {
-            self.start = self.current;
+            self.begin_token();
             self.scan_token();
         }
+
+        self.begin_token();
+        self.add_token(EOF, Object::Placeholder);
+        (
+            std::mem::take(&mut self.tokens),
+            std::mem::take(&mut self.errors),
+        )
+    }
+
+    fn begin_token(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.start - self.line_start + 1;
+    }
+
+    /// 当前 token 覆盖的区间
+    fn span(&self) -> Span {
+        let offset = self.offsets[self.start];
+        Span::new(
+            offset,
+            self.offsets[self.current] - offset,
+            self.start_line,
+            self.start_column,
+        )
+    }
+
+    fn newline(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
     }
 
     fn is_at_end(&self) -> bool {
@@ -54,7 +114,7 @@ impl Lexer {
     }
 
     pub fn peek_next(&mut self) -> char {
-        if self.current + 1 > self.source.len() {
+        if self.current + 1 >= self.source.len() {
             return '\0';
         };
         self.get_ch(self.current + 1)
@@ -71,7 +131,27 @@ impl Lexer {
             .unwrap()
             .iter()
             .collect();
-        self.tokens.push(Token::new(tag, text, literal, self.line));
+        let span = self.span();
+        let mut token = Token::new(tag, text, literal, self.line).with_span(span);
+        token.trivia = std::mem::take(&mut self.trivia);
+        self.tokens.push(token);
+    }
+
+    fn add_trivia(&mut self, trivia: Trivia) {
+        if !self.lossless {
+            return;
+        }
+        match (self.trivia.last_mut(), trivia) {
+            (Some(Trivia::Whitespace(last)), Trivia::Whitespace(text)) => last.push_str(&text),
+            (_, trivia) => self.trivia.push(trivia),
+        }
+    }
+
+    /// 记录错误，同时产生一个 ERROR token 交给语法分析器跳过
+    fn add_error(&mut self, kind: LexErrorKind) {
+        let span = self.span();
+        self.errors.push(LexError { kind, span });
+        self.add_token(ERROR, Object::Placeholder);
     }
 
     fn scan_token(&mut self) {
@@ -113,20 +193,29 @@ impl Lexer {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    let text = self.get_by_range(self.start, self.current);
+                    // \r\n 换行时 \r 不属于注释
+                    match text.strip_suffix('\r') {
+                        Some(comment) => {
+                            self.add_trivia(Trivia::Comment(comment.to_string()));
+                            self.add_trivia(Trivia::Whitespace("\r".to_string()));
+                        }
+                        None => self.add_trivia(Trivia::Comment(text)),
+                    }
                 } else {
                     self.add_token(SLASH, Object::Placeholder);
                 }
             }
             '"' => {
                 while self.peek() != '"' && !self.is_at_end() {
-                    if self.peek() == '\n' {
-                        self.line += 1
+                    let c = self.advance();
+                    if c == '\n' {
+                        self.newline();
                     }
-                    self.advance();
                 }
 
                 if self.is_at_end() {
-                    panic!("unterminated string");
+                    return self.add_error(LexErrorKind::UnterminatedString);
                 }
 
                 self.advance();
@@ -134,8 +223,11 @@ impl Lexer {
 
                 self.add_token(STRING, Object::String(value));
             }
-            '\n' => self.line += 1,
-            ' ' | '\r' | '\t' => { /* ignore white space */ }
+            '\n' => {
+                self.newline();
+                self.add_trivia(Trivia::Newline);
+            }
+            ' ' | '\r' | '\t' => self.add_trivia(Trivia::Whitespace(c.to_string())),
             c => {
                 if c.is_numeric() {
                     // 一直到不为数字为止
@@ -152,12 +244,18 @@ impl Lexer {
                         }
                     }
 
-                    let value = self
-                        .get_by_range(self.start, self.current)
-                        .parse::<f32>()
-                        .expect("解析数字出错");
+                    // 紧跟在数字后的字母也算作数字的一部分，例如 123abc
+                    let mut malformed = false;
+                    while self.peek().is_alphanumeric() {
+                        malformed = true;
+                        self.advance();
+                    }
 
-                    self.add_token(NUMBER, Object::Digit(value));
+                    let text = self.get_by_range(self.start, self.current);
+                    match text.parse::<f32>() {
+                        Ok(value) if !malformed => self.add_token(NUMBER, Object::Digit(value)),
+                        _ => self.add_error(LexErrorKind::MalformedNumber(text)),
+                    }
                 } else if c.is_ascii_alphanumeric() {
                     // 忽略下划线的关键字
                     while self.peek().is_ascii_alphanumeric() {
@@ -170,13 +268,13 @@ impl Lexer {
                         self.add_token(IDENTIFIER, Object::Placeholder);
                     }
                 } else {
-                    println!("unexpected token: {}", c)
+                    self.add_error(LexErrorKind::UnexpectedCharacter(c))
                 }
             }
         }
     }
 
-    ///
+    /// 如果下一个字符符合预期，指针后移
     fn expect(&mut self, expected: char) -> bool {
         if self.is_at_end() {
             return false;
@@ -188,6 +286,87 @@ impl Lexer {
 
         self.current += 1;
 
-        return true;
+        true
     }
 }
+
+#[test]
+fn token_span() {
+    let mut l = Lexer::new(String::from("var a = \"é\nb\";\n  print a;"));
+    let (tokens, _) = l.scan_tokens();
+
+    let spans: Vec<(&str, Span)> = tokens.iter().map(|t| (t.lexeme.as_str(), t.span)).collect();
+
+    assert_eq!(spans[0], ("var", Span::new(0, 3, 1, 1)));
+    assert_eq!(spans[1], ("a", Span::new(4, 1, 1, 5)));
+    assert_eq!(spans[3], ("\"é\nb\"", Span::new(8, 6, 1, 9)));
+    assert_eq!(spans[4], (";", Span::new(14, 1, 2, 3)));
+    assert_eq!(spans[5], ("print", Span::new(18, 5, 3, 3)));
+    assert_eq!(spans[6], ("a", Span::new(24, 1, 3, 9)));
+}
+
+#[test]
+fn lex_error() {
+    let mut l = Lexer::new(String::from("var a = 1 @ 2;\nvar b = 12ab;\nprint \"abc"));
+    let (tokens, errors) = l.scan_tokens();
+
+    let kinds: Vec<LexErrorKind> = errors.iter().map(|e| e.kind.clone()).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            LexErrorKind::UnexpectedCharacter('@'),
+            LexErrorKind::MalformedNumber("12ab".to_string()),
+            LexErrorKind::UnterminatedString,
+        ]
+    );
+    assert_eq!(errors[0].span, Span::new(10, 1, 1, 11));
+    assert_eq!(
+        errors[1].to_string(),
+        "[line 2] Error: malformed number '12ab'"
+    );
+    assert_eq!(errors[2].span.len, 4);
+
+    let error_tokens: Vec<&str> = tokens
+        .iter()
+        .filter(|t| t.tag == ERROR)
+        .map(|t| t.lexeme.as_str())
+        .collect();
+    assert_eq!(error_tokens, vec!["@", "12ab", "\"abc"]);
+}
+
+#[test]
+fn lossless() {
+    let source = "var a = 1; // one\n\n\t// two\r\nprint a;\n";
+    let (tokens, _) = Lexer::new(String::from(source)).lossless().scan_tokens();
+
+    assert_eq!(
+        tokens[5].trivia,
+        vec![
+            Trivia::Whitespace(" ".to_string()),
+            Trivia::Comment("// one".to_string()),
+            Trivia::Newline,
+            Trivia::Newline,
+            Trivia::Whitespace("\t".to_string()),
+            Trivia::Comment("// two".to_string()),
+            Trivia::Whitespace("\r".to_string()),
+            Trivia::Newline,
+        ]
+    );
+    assert_eq!(tokens[8].trivia, vec![Trivia::Newline]);
+
+    // 拼接后得到原始源码
+    let mut text = String::new();
+    for token in &tokens {
+        for trivia in &token.trivia {
+            match trivia {
+                Trivia::Whitespace(s) | Trivia::Comment(s) => text.push_str(s),
+                Trivia::Newline => text.push('\n'),
+            }
+        }
+        text.push_str(&token.lexeme);
+    }
+    assert_eq!(text, source);
+
+    let (tokens, _) = Lexer::new(String::from(source)).scan_tokens();
+    assert!(tokens.iter().all(|t| t.trivia.is_empty()));
+}
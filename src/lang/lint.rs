@@ -0,0 +1,476 @@
+// 基于 AST 的代码检查，不影响程序能否运行。
+//
+// 每个 lint 有固定的名字和默认级别，可以在运行时通过 LintConfig 修改，
+// 也可以在源码中用注释修改，作用于整个文件：
+//
+//     // lint: allow(shadowing)
+//     // lint: deny(unused_variable, self_assignment)
+
+use std::collections::HashMap;
+
+use super::{
+    ast::{Expression, Statement},
+    diagnostic::{Diagnostic, Severity},
+    lexer::Lexer,
+    token::{Span, Token, TokenType::*, Trivia},
+    visitor::{walk_expr, walk_stmt, ExprVisitor, StmtVisitor},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Level> {
+        [Level::Allow, Level::Warn, Level::Deny]
+            .iter()
+            .copied()
+            .find(|l| l.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// 声明后从未读取的局部变量和参数
+    UnusedVariable,
+    /// return 之后的语句
+    UnreachableCode,
+    /// 局部变量与外层变量同名
+    Shadowing,
+    /// if 的条件是常量
+    ConstantCondition,
+    /// a = a
+    SelfAssignment,
+    /// a == a
+    SelfComparison,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 6] = [
+        Lint::UnusedVariable,
+        Lint::UnreachableCode,
+        Lint::Shadowing,
+        Lint::ConstantCondition,
+        Lint::SelfAssignment,
+        Lint::SelfComparison,
+    ];
+
+    /// 用于配置的名字，保持不变
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused_variable",
+            Lint::UnreachableCode => "unreachable_code",
+            Lint::Shadowing => "shadowing",
+            Lint::ConstantCondition => "constant_condition",
+            Lint::SelfAssignment => "self_assignment",
+            Lint::SelfComparison => "self_comparison",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.iter().copied().find(|l| l.name() == name)
+    }
+
+    pub fn default_level(&self) -> Level {
+        match self {
+            // Lox 中在块里复用外层的名字很常见
+            Lint::Shadowing => Level::Allow,
+            _ => Level::Warn,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<Lint, Level>,
+}
+
+impl LintConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) -> &mut Self {
+        self.levels.insert(lint, level);
+        self
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.levels
+            .get(&lint)
+            .copied()
+            .unwrap_or_else(|| lint.default_level())
+    }
+
+    /// 读取源码中 `// lint: level(name, ...)` 形式的注释，
+    /// 覆盖之前的设置。返回无法识别的 lint 名字
+    pub fn apply_source(&mut self, source: &str) -> Vec<String> {
+        // 注释来自 lossless 词法分析，字符串中的 // 不会被当成注释
+        let (tokens, _) = Lexer::new(source.to_string()).lossless().scan_tokens();
+        let comments = tokens
+            .iter()
+            .flat_map(|token| &token.trivia)
+            .filter_map(|trivia| match trivia {
+                Trivia::Comment(text) => Some(text),
+                _ => None,
+            });
+
+        let mut unknown = vec![];
+        for comment in comments {
+            let directive = match comment
+                .strip_prefix("//")
+                .and_then(|text| text.trim().strip_prefix("lint:"))
+            {
+                Some(directive) => directive.trim(),
+                None => continue,
+            };
+            let (level, names) = match directive.find('(') {
+                Some(i) if directive.ends_with(')') => {
+                    (&directive[..i], &directive[i + 1..directive.len() - 1])
+                }
+                _ => {
+                    unknown.push(directive.to_string());
+                    continue;
+                }
+            };
+            let level = match Level::from_name(level.trim()) {
+                Some(level) => level,
+                None => {
+                    unknown.push(level.trim().to_string());
+                    continue;
+                }
+            };
+            for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                match Lint::from_name(name) {
+                    Some(lint) => {
+                        self.set(lint, level);
+                    }
+                    None => unknown.push(name.to_string()),
+                }
+            }
+        }
+        unknown
+    }
+}
+
+/// 一条检查结果，level 不会是 Allow
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintMessage {
+    pub lint: Lint,
+    pub level: Level,
+    pub span: Span,
+    pub message: String,
+}
+
+impl From<&LintMessage> for Diagnostic {
+    fn from(message: &LintMessage) -> Self {
+        let severity = match message.level {
+            Level::Deny => Severity::Error,
+            _ => Severity::Warning,
+        };
+        Diagnostic::new(severity, message.message.clone(), message.span).with_note(format!(
+            "`lint: {}({})` is in effect",
+            message.level.name(),
+            message.lint.name()
+        ))
+    }
+}
+
+struct Local {
+    name: Token,
+    used: bool,
+    parameter: bool,
+}
+
+pub struct Linter {
+    config: LintConfig,
+    /// 第一层是全局作用域，其中的名字不检查是否使用
+    scopes: Vec<Vec<Local>>,
+    messages: Vec<LintMessage>,
+}
+
+impl Linter {
+    pub fn new(config: LintConfig) -> Self {
+        Linter {
+            config,
+            scopes: vec![],
+            messages: vec![],
+        }
+    }
+
+    pub fn lint(&mut self, statements: &[Statement]) -> Vec<LintMessage> {
+        self.scopes = vec![vec![]];
+        self.visit_stmts(statements);
+        self.scopes.clear();
+
+        let mut messages = std::mem::take(&mut self.messages);
+        messages.sort_by_key(|m| m.span.offset);
+        messages
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(vec![]);
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap_or_default();
+        for local in scope {
+            if local.used {
+                continue;
+            }
+            let kind = if local.parameter {
+                "parameter"
+            } else {
+                "variable"
+            };
+            self.report(
+                Lint::UnusedVariable,
+                local.name.span,
+                format!("unused {} '{}'", kind, local.name.lexeme),
+            );
+        }
+    }
+
+    fn declare(&mut self, name: &Token, parameter: bool) {
+        let depth = self.scopes.len();
+        if depth > 1 {
+            let shadowed = self.scopes[..depth - 1]
+                .iter()
+                .flatten()
+                .any(|l| l.name.lexeme == name.lexeme);
+            if shadowed {
+                self.report(
+                    Lint::Shadowing,
+                    name.span,
+                    format!("'{}' shadows a variable in an outer scope", name.lexeme),
+                );
+            }
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Local {
+                name: name.clone(),
+                used: false,
+                parameter,
+            });
+        }
+    }
+
+    fn use_variable(&mut self, name: &Token) {
+        let local = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .flat_map(|scope| scope.iter_mut().rev())
+            .find(|l| l.name.lexeme == name.lexeme);
+        if let Some(local) = local {
+            local.used = true;
+        }
+    }
+
+    fn report<T: Into<String>>(&mut self, lint: Lint, span: Span, message: T) {
+        let level = self.config.level(lint);
+        if level != Level::Allow {
+            self.messages.push(LintMessage {
+                lint,
+                level,
+                span,
+                message: message.into(),
+            });
+        }
+    }
+}
+
+impl<'ast> StmtVisitor<'ast> for Linter {
+    type StmtOutput = ();
+
+    fn visit_stmts(&mut self, statements: &'ast [Statement]) {
+        // 只报告 return 之后的第一条语句
+        let unreachable = statements
+            .windows(2)
+            .find(|pair| matches!(pair[0], Statement::Return(..)));
+        if let Some(pair) = unreachable {
+            self.report(
+                Lint::UnreachableCode,
+                pair[1].span(),
+                "unreachable statement",
+            );
+        }
+        for stmt in statements {
+            self.visit_stmt(stmt);
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Statement) {
+        match stmt {
+            Statement::Block(statements, _) => {
+                self.begin_scope();
+                self.visit_stmts(statements);
+                self.end_scope();
+            }
+            Statement::Var(name, initializer, _) => {
+                if let Some(initializer) = initializer {
+                    self.visit_expr(initializer);
+                }
+                self.declare(name, false);
+            }
+            Statement::Function(name, parameters, body, _) => {
+                self.declare(name, false);
+                self.begin_scope();
+                for param in parameters {
+                    self.declare(param, true);
+                }
+                self.visit_stmts(body);
+                self.end_scope();
+            }
+            Statement::If(_, condition, ..) => {
+                if constant(condition) {
+                    self.report(
+                        Lint::ConstantCondition,
+                        condition.span(),
+                        "condition is always the same",
+                    );
+                }
+                walk_stmt(self, stmt);
+            }
+            _ => walk_stmt(self, stmt),
+        }
+    }
+}
+
+impl<'ast> ExprVisitor<'ast> for Linter {
+    type Output = ();
+
+    fn visit_expr(&mut self, expr: &'ast Expression) {
+        match expr {
+            Expression::Var(name, _) => self.use_variable(name),
+            Expression::Assignment(name, value, _) => {
+                if let Expression::Var(other, _) = value.as_ref() {
+                    if other.lexeme == name.lexeme {
+                        self.report(
+                            Lint::SelfAssignment,
+                            expr.span(),
+                            format!("'{}' is assigned to itself", name.lexeme),
+                        );
+                    }
+                }
+                // 赋值不算作读取
+                walk_expr(self, expr);
+            }
+            Expression::Binary(left, operator, right) => {
+                let comparison = matches!(
+                    operator.tag,
+                    EQUAL_EQUAL | BANG_EQUAL | GREATER | GREATER_EQUAL | LESS | LESS_EQUAL
+                );
+                if let (true, Expression::Var(l, _), Expression::Var(r, _)) =
+                    (comparison, left.as_ref(), right.as_ref())
+                {
+                    if l.lexeme == r.lexeme {
+                        self.report(
+                            Lint::SelfComparison,
+                            expr.span(),
+                            format!("'{}' is compared with itself", l.lexeme),
+                        );
+                    }
+                }
+                walk_expr(self, expr);
+            }
+            _ => walk_expr(self, expr),
+        }
+    }
+}
+
+/// 不依赖任何变量、结果固定的表达式
+fn constant(expr: &Expression) -> bool {
+    match expr {
+        Expression::Literal(..) => true,
+        Expression::Grouping(inner, _) | Expression::Unary(_, inner) => constant(inner),
+        Expression::Binary(left, _, right) | Expression::Logical(left, _, right) => {
+            constant(left) && constant(right)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+fn lint_source(source: &str, config: LintConfig) -> Vec<String> {
+    use super::{lexer::Lexer, parser::Parser};
+
+    let (tokens, _) = Lexer::new(String::from(source)).scan_tokens();
+    let (statements, errors) = Parser::new(tokens).parse();
+    assert!(errors.is_empty(), "{:?}", errors);
+    Linter::new(config)
+        .lint(&statements)
+        .iter()
+        .map(|m| format!("{} {}: {}", m.level.name(), m.lint.name(), m.message))
+        .collect()
+}
+
+#[test]
+fn lints() {
+    let source = "var a = 1;
+        fun f(x, y) {
+            var b = 2;
+            var a = b;
+            a = a;
+            if (a == a) print a;
+            if (!(1 < 2)) print 1;
+            return x;
+            print 3;
+        }";
+
+    assert_eq!(
+        lint_source(source, LintConfig::new()),
+        vec![
+            "warn unused_variable: unused parameter 'y'",
+            "warn self_assignment: 'a' is assigned to itself",
+            "warn self_comparison: 'a' is compared with itself",
+            "warn constant_condition: condition is always the same",
+            "warn unreachable_code: unreachable statement",
+        ]
+    );
+
+    let mut config = LintConfig::new();
+    config
+        .set(Lint::Shadowing, Level::Deny)
+        .set(Lint::SelfComparison, Level::Allow);
+    let messages = lint_source(source, config);
+    assert!(
+        messages.contains(&"deny shadowing: 'a' shadows a variable in an outer scope".to_string())
+    );
+    assert!(!messages.iter().any(|m| m.contains("self_comparison")));
+}
+
+#[test]
+fn source_directives() {
+    let source = "// lint: allow(unused_variable, constant_condition)
+        // lint: deny(self_assignment)
+        // lint: warn(no_such_lint)
+        { var a = 1; a = a; if (true) print 1; }";
+
+    let mut config = LintConfig::new();
+    config.set(Lint::SelfAssignment, Level::Allow);
+    assert_eq!(config.apply_source(source), vec!["no_such_lint"]);
+    assert_eq!(config.level(Lint::UnusedVariable), Level::Allow);
+    assert_eq!(config.level(Lint::Shadowing), Level::Allow);
+    assert_eq!(
+        lint_source(source, config),
+        vec!["deny self_assignment: 'a' is assigned to itself"]
+    );
+
+    // 字符串中的 // 不是注释
+    let source = "print \"// lint: allow(unused_variable)\"; { var b; }";
+    let mut config = LintConfig::new();
+    assert!(config.apply_source(source).is_empty());
+    assert_eq!(config.level(Lint::UnusedVariable), Level::Warn);
+}
@@ -1,6 +1,21 @@
+// 错误里携带完整的 token 以便定位，体积较大是有意为之
+#![allow(clippy::result_large_err)]
+
 pub mod ast;
 pub mod compiler;
+pub mod diagnostic;
+pub mod dump;
+pub mod engine;
 pub mod environment;
+pub mod error;
+pub mod formatter;
+pub mod json;
 pub mod lexer;
+pub mod lint;
+pub mod operator;
 pub mod parser;
+pub mod printer;
+pub mod repl;
+pub mod resolver;
 pub mod token;
+pub mod visitor;
@@ -0,0 +1,223 @@
+// 运算符的语义与具体的执行方式无关，按 TokenType 分发，
+// 出错时只返回错误信息，由调用方补充位置。
+
+use super::token::{
+    Object,
+    TokenType::{self, *},
+    UnionObject,
+};
+
+pub type BinaryFn = for<'a> fn(&UnionObject<'a>, &UnionObject<'a>) -> Result<Object, &'static str>;
+pub type UnaryFn = for<'a> fn(&UnionObject<'a>) -> Result<Object, &'static str>;
+
+pub static BINARY_OPERATORS: &[(TokenType, BinaryFn)] = &[
+    (PLUS, add),
+    (MINUS, subtract),
+    (STAR, multiply),
+    (SLASH, divide),
+    (GREATER, greater),
+    (GREATER_EQUAL, greater_equal),
+    (LESS, less),
+    (LESS_EQUAL, less_equal),
+    (EQUAL_EQUAL, equal),
+    (BANG_EQUAL, not_equal),
+];
+
+pub static UNARY_OPERATORS: &[(TokenType, UnaryFn)] = &[(MINUS, negate), (BANG, not)];
+
+pub fn binary(tag: TokenType) -> Option<BinaryFn> {
+    BINARY_OPERATORS
+        .iter()
+        .find(|(t, _)| *t == tag)
+        .map(|(_, f)| *f)
+}
+
+pub fn unary(tag: TokenType) -> Option<UnaryFn> {
+    UNARY_OPERATORS
+        .iter()
+        .find(|(t, _)| *t == tag)
+        .map(|(_, f)| *f)
+}
+
+fn numbers(left: &UnionObject, right: &UnionObject) -> Result<(f32, f32), &'static str> {
+    match (left, right) {
+        (UnionObject::Value(Object::Digit(l)), UnionObject::Value(Object::Digit(r))) => {
+            Ok((*l, *r))
+        }
+        _ => Err("Operands must be numbers"),
+    }
+}
+
+fn add(left: &UnionObject, right: &UnionObject) -> Result<Object, &'static str> {
+    match (left, right) {
+        (UnionObject::Value(Object::String(l)), UnionObject::Value(Object::String(r))) => {
+            Ok(Object::String(format!("{}{}", l, r)))
+        }
+        (UnionObject::Value(Object::Digit(l)), UnionObject::Value(Object::Digit(r))) => {
+            Ok(Object::Digit(l + r))
+        }
+        _ => Err("Operands must be two numbers or two strings"),
+    }
+}
+
+fn subtract(left: &UnionObject, right: &UnionObject) -> Result<Object, &'static str> {
+    numbers(left, right).map(|(l, r)| Object::Digit(l - r))
+}
+
+fn multiply(left: &UnionObject, right: &UnionObject) -> Result<Object, &'static str> {
+    numbers(left, right).map(|(l, r)| Object::Digit(l * r))
+}
+
+fn divide(left: &UnionObject, right: &UnionObject) -> Result<Object, &'static str> {
+    numbers(left, right).map(|(l, r)| Object::Digit(l / r))
+}
+
+fn greater(left: &UnionObject, right: &UnionObject) -> Result<Object, &'static str> {
+    numbers(left, right).map(|(l, r)| Object::Bool(l > r))
+}
+
+fn greater_equal(left: &UnionObject, right: &UnionObject) -> Result<Object, &'static str> {
+    numbers(left, right).map(|(l, r)| Object::Bool(l >= r))
+}
+
+fn less(left: &UnionObject, right: &UnionObject) -> Result<Object, &'static str> {
+    numbers(left, right).map(|(l, r)| Object::Bool(l < r))
+}
+
+fn less_equal(left: &UnionObject, right: &UnionObject) -> Result<Object, &'static str> {
+    numbers(left, right).map(|(l, r)| Object::Bool(l <= r))
+}
+
+fn equal<'a>(left: &UnionObject<'a>, right: &UnionObject<'a>) -> Result<Object, &'static str> {
+    Ok(Object::Bool(left.equals(right)))
+}
+
+fn not_equal<'a>(left: &UnionObject<'a>, right: &UnionObject<'a>) -> Result<Object, &'static str> {
+    Ok(Object::Bool(!left.equals(right)))
+}
+
+fn negate(operand: &UnionObject) -> Result<Object, &'static str> {
+    match operand {
+        UnionObject::Value(Object::Digit(n)) => Ok(Object::Digit(-n)),
+        _ => Err("Operand must be a number"),
+    }
+}
+
+fn not(operand: &UnionObject) -> Result<Object, &'static str> {
+    Ok(Object::Bool(!operand.is_truthy()))
+}
+
+#[test]
+fn operator_table() {
+    use super::{ast::Statement, environment::Environment, token::Closure};
+    use std::{cell::RefCell, rc::Rc};
+
+    let closure = |name: &str| {
+        let name = super::token::Token::new(IDENTIFIER, name, Object::Placeholder, 1);
+        UnionObject::Function(Closure {
+            declaration: Rc::new(Statement::Function(
+                name,
+                vec![],
+                vec![],
+                Default::default(),
+            )),
+            closure: Rc::new(RefCell::new(Environment::new(None))),
+        })
+    };
+
+    let n = |v: f32| UnionObject::Value(Object::Digit(v));
+    let s = |v: &str| UnionObject::Value(Object::String(v.to_string()));
+    let b = |v: bool| UnionObject::Value(Object::Bool(v));
+    let nil = UnionObject::Value(Object::Nil);
+    let f = closure("f");
+    let g = closure("g");
+
+    const NUMBERS: &str = "Operands must be numbers";
+    use Object::{Bool, Digit};
+
+    let binary_cases: Vec<(TokenType, UnionObject, UnionObject, Result<Object, &str>)> = vec![
+        (PLUS, n(1.0), n(2.0), Ok(Digit(3.0))),
+        (PLUS, s("a"), s("b"), Ok(Object::String("ab".to_string()))),
+        (
+            PLUS,
+            s("a"),
+            n(1.0),
+            Err("Operands must be two numbers or two strings"),
+        ),
+        (
+            PLUS,
+            nil.clone(),
+            nil.clone(),
+            Err("Operands must be two numbers or two strings"),
+        ),
+        (MINUS, n(3.0), n(1.0), Ok(Digit(2.0))),
+        (MINUS, s("a"), n(1.0), Err(NUMBERS)),
+        (STAR, n(3.0), n(2.0), Ok(Digit(6.0))),
+        (STAR, b(true), n(2.0), Err(NUMBERS)),
+        (SLASH, n(3.0), n(2.0), Ok(Digit(1.5))),
+        (SLASH, n(3.0), nil.clone(), Err(NUMBERS)),
+        (GREATER, n(2.0), n(1.0), Ok(Bool(true))),
+        (GREATER, s("b"), s("a"), Err(NUMBERS)),
+        (GREATER_EQUAL, n(1.0), n(1.0), Ok(Bool(true))),
+        (GREATER_EQUAL, f.clone(), n(1.0), Err(NUMBERS)),
+        (LESS, n(1.0), n(2.0), Ok(Bool(true))),
+        (LESS, n(1.0), b(false), Err(NUMBERS)),
+        (LESS_EQUAL, n(2.0), n(1.0), Ok(Bool(false))),
+        (LESS_EQUAL, nil.clone(), n(1.0), Err(NUMBERS)),
+        (EQUAL_EQUAL, n(1.0), n(1.0), Ok(Bool(true))),
+        (EQUAL_EQUAL, n(1.0), s("1"), Ok(Bool(false))),
+        (EQUAL_EQUAL, s("a"), s("a"), Ok(Bool(true))),
+        (EQUAL_EQUAL, nil.clone(), nil.clone(), Ok(Bool(true))),
+        (EQUAL_EQUAL, nil.clone(), b(false), Ok(Bool(false))),
+        (EQUAL_EQUAL, f.clone(), f.clone(), Ok(Bool(true))),
+        (EQUAL_EQUAL, f.clone(), g.clone(), Ok(Bool(false))),
+        (BANG_EQUAL, n(1.0), n(2.0), Ok(Bool(true))),
+        (BANG_EQUAL, b(true), b(true), Ok(Bool(false))),
+        (BANG_EQUAL, f.clone(), nil.clone(), Ok(Bool(true))),
+    ];
+
+    let unary_cases: Vec<(TokenType, UnionObject, Result<Object, &str>)> = vec![
+        (MINUS, n(2.0), Ok(Digit(-2.0))),
+        (MINUS, s("a"), Err("Operand must be a number")),
+        (MINUS, nil.clone(), Err("Operand must be a number")),
+        (BANG, b(true), Ok(Bool(false))),
+        (BANG, nil.clone(), Ok(Bool(true))),
+        (BANG, n(0.0), Ok(Bool(false))),
+        (BANG, f.clone(), Ok(Bool(false))),
+    ];
+
+    for (tag, left, right, expected) in &binary_cases {
+        let operator = binary(*tag).unwrap_or_else(|| panic!("missing {:?}", tag));
+        assert_eq!(
+            &operator(left, right),
+            expected,
+            "{:?} {} {}",
+            tag,
+            left,
+            right
+        );
+    }
+    for (tag, operand, expected) in &unary_cases {
+        let operator = unary(*tag).unwrap_or_else(|| panic!("missing {:?}", tag));
+        assert_eq!(&operator(operand), expected, "{:?} {}", tag, operand);
+    }
+
+    // 表中的每个运算符都有用例覆盖
+    for (tag, _) in BINARY_OPERATORS {
+        assert!(
+            binary_cases.iter().any(|c| c.0 == *tag),
+            "untested {:?}",
+            tag
+        );
+    }
+    for (tag, _) in UNARY_OPERATORS {
+        assert!(
+            unary_cases.iter().any(|c| c.0 == *tag),
+            "untested {:?}",
+            tag
+        );
+    }
+    assert_eq!(BINARY_OPERATORS.len(), 10);
+    assert_eq!(UNARY_OPERATORS.len(), 2);
+    assert!(binary(EQUAL).is_none());
+}
@@ -2,8 +2,9 @@ use std::fmt::Display;
 
 use super::{
     ast::{Expression, Statement},
+    error::ParseError,
     token::{
-        Object, Token,
+        Object, Span, Token,
         TokenType::{self, *},
     },
 };
@@ -11,23 +12,41 @@ use super::{
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    errors: Vec<ParseError>,
 }
 
+type ParseResult<T> = Result<T, ParseError>;
+
 #[allow(dead_code)]
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+    pub fn new(mut tokens: Vec<Token>) -> Self {
+        // 保证总是以 EOF 结尾，peek 永远不会越界
+        if tokens.last().map(|t| t.tag) != Some(EOF) {
+            let end = tokens.last().map_or(Span::new(0, 0, 1, 1), |t| {
+                Span::new(t.span.end(), 0, t.span.line, t.span.column + t.span.len)
+            });
+            tokens.push(Token::new(EOF, "", Object::Placeholder, end.line).with_span(end));
+        }
+
+        Parser {
+            tokens,
+            current: 0,
+            errors: vec![],
+        }
     }
 
-    pub fn parse(&mut self) -> Vec<Statement> {
+    /// 返回能够解析出的语句，以及解析过程中遇到的全部错误
+    pub fn parse(&mut self) -> (Vec<Statement>, Vec<ParseError>) {
         let mut statements: Vec<Statement> = vec![];
         while !self.is_at_end() {
-            statements.push(self.declaration())
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
         }
-        statements
+        (statements, std::mem::take(&mut self.errors))
     }
 
-    fn statement(&mut self) -> Statement {
+    fn statement(&mut self) -> ParseResult<Statement> {
         if self.expect(vec![IF]) {
             return self.ifstmt();
         }
@@ -36,106 +55,225 @@ impl Parser {
             return self.print();
         }
 
+        if self.expect(vec![RETURN]) {
+            return self.return_stmt();
+        }
+
+        if self.expect(vec![WHILE]) {
+            return self.while_stmt();
+        }
+
+        if self.expect(vec![FOR]) {
+            return self.for_stmt();
+        }
+
         if self.expect(vec![LEFT_BRACE]) {
-            return Statement::Block(self.block());
+            let start = self.previous().span;
+            let statements = self.block()?;
+            return Ok(Statement::Block(statements, self.span_from(start)));
         }
 
         self.expression_statement()
     }
 
-    fn block(&mut self) -> Vec<Statement> {
+    fn block(&mut self) -> ParseResult<Vec<Statement>> {
         let mut statements = vec![];
         while !self.check(RIGHT_BRACE) && !self.is_at_end() {
-            statements.push(self.declaration())
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
         }
-        self.consume(RIGHT_BRACE, "expected '}' after block.");
-        statements
+        self.consume(RIGHT_BRACE, "expect '}' after block")?;
+        Ok(statements)
     }
 
-    fn declaration(&mut self) -> Statement {
-        if self.expect(vec![FUNCTION]) {
-            return self.function("function");
-        }
+    /// 声明是错误恢复的边界：出错后记录错误并同步到下一条语句
+    fn declaration(&mut self) -> Option<Statement> {
+        let result = if self.expect(vec![FUN, FUNCTION]) {
+            self.function("function")
+        } else if self.expect(vec![VAR]) {
+            self.var()
+        } else {
+            self.statement()
+        };
 
-        if self.expect(vec![VAR]) {
-            return self.var();
+        match result {
+            Ok(stmt) => Some(stmt),
+            Err(error) => {
+                self.report(error);
+                self.synchronize();
+                None
+            }
         }
-
-        self.statement()
     }
 
-    fn assignment(&mut self) -> Expression {
-        let expr = self.equality();
+    fn assignment(&mut self) -> ParseResult<Expression> {
+        let expr = self.or()?;
         if self.expect(vec![EQUAL]) {
-            // let eq = self.previous();
-            let value = self.assignment();
-            if let Expression::Var(token) = expr {
-                return Expression::Assignment(token, Box::new(value));
+            let equals = self.previous();
+            let value = self.assignment()?;
+            if let Expression::Var(token, _) = expr {
+                return Ok(Expression::Assignment(token, Box::new(value), None));
             }
-            panic!("invalid assignment")
+            // 解析器状态没有混乱，只记录错误，不需要同步
+            self.report(ParseError::new(equals, "invalid assignment target"));
         }
 
-        return expr;
+        Ok(expr)
     }
 
-    fn print(&mut self) -> Statement {
-        let value = self.expression();
+    fn print(&mut self) -> ParseResult<Statement> {
+        let start = self.previous().span;
+        let value = self.expression()?;
+        let span = self.span_from(start);
+        self.expect(vec![SEMICOLON]);
 
-        Statement::Print(value)
+        Ok(Statement::Print(value, span))
     }
 
-    fn ifstmt(&mut self) -> Statement {
-        self.consume(LEFT_PAREN, "expect '(' after 'if' ");
-        let condition = self.expression();
-        self.consume(RIGHT_PAREN, "expect ')' after if condition");
-        let then_block = self.statement();
+    fn return_stmt(&mut self) -> ParseResult<Statement> {
+        let keyword = self.previous();
+        // 分号可以省略，遇到 } 或结尾说明没有返回值
+        let value = if self.check(SEMICOLON) || self.check(RIGHT_BRACE) || self.is_at_end() {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.expect(vec![SEMICOLON]);
+
+        Ok(Statement::Return(keyword, value))
+    }
+
+    fn while_stmt(&mut self) -> ParseResult<Statement> {
+        let keyword = self.previous();
+        self.consume(LEFT_PAREN, "expect '(' after 'while'")?;
+        let condition = self.expression()?;
+        self.consume(RIGHT_PAREN, "expect ')' after while condition")?;
+        let body = self.statement()?;
+
+        Ok(Statement::While(keyword, condition, Box::new(body)))
+    }
+
+    /// for (init; cond; incr) body 转换为
+    /// { init; while (cond) { body; incr; } }
+    fn for_stmt(&mut self) -> ParseResult<Statement> {
+        let keyword = self.previous();
+        self.consume(LEFT_PAREN, "expect '(' after 'for'")?;
+
+        let initializer = if self.expect(vec![SEMICOLON]) {
+            None
+        } else {
+            let stmt = if self.expect(vec![VAR]) {
+                self.var()?
+            } else {
+                self.expression_statement()?
+            };
+            if self.previous().tag != SEMICOLON {
+                return Err(self.error("expect ';' after loop initializer"));
+            }
+            Some(stmt)
+        };
+
+        let condition = if self.check(SEMICOLON) {
+            Expression::Literal(Object::Bool(true), keyword.span)
+        } else {
+            self.expression()?
+        };
+        self.consume(SEMICOLON, "expect ';' after loop condition")?;
+
+        let increment = if self.check(RIGHT_PAREN) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(RIGHT_PAREN, "expect ')' after for clauses")?;
+
+        let mut body = self.statement()?;
+        let span = keyword.span.to(body.span());
+
+        if let Some(increment) = increment {
+            let body_span = body.span();
+            body = Statement::Block(vec![body, Statement::Expression(increment)], body_span);
+        }
+        body = Statement::While(keyword, condition, Box::new(body));
+        if let Some(initializer) = initializer {
+            body = Statement::Block(vec![initializer, body], span);
+        }
+
+        Ok(body)
+    }
+
+    fn ifstmt(&mut self) -> ParseResult<Statement> {
+        let keyword = self.previous();
+        self.consume(LEFT_PAREN, "expect '(' after 'if'")?;
+        let condition = self.expression()?;
+        self.consume(RIGHT_PAREN, "expect ')' after if condition")?;
+        let then_block = self.statement()?;
         let else_block = if self.expect(vec![ELSE]) {
-            Some(self.statement())
+            Some(self.statement()?)
         } else {
             None
         };
 
-        Statement::If(
+        Ok(Statement::If(
+            keyword,
             condition,
             Box::new(then_block),
-            else_block.map(|s| Box::new(s)),
-        )
+            else_block.map(Box::new),
+        ))
     }
 
-    fn var(&mut self) -> Statement {
-        let name = self.consume(IDENTIFIER, "expect var name");
-        if self.expect(vec![EQUAL]) {
-            return Statement::Var(name, self.expression());
-        }
+    fn var(&mut self) -> ParseResult<Statement> {
+        let start = self.previous().span;
+        let name = self.consume(IDENTIFIER, "expect variable name")?;
+        let initializer = if self.expect(vec![EQUAL]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        let span = self.span_from(start);
+        self.expect(vec![SEMICOLON]);
 
-        panic!("var error")
+        Ok(Statement::Var(name, initializer, span))
     }
 
-    fn function(&mut self, kind: &str) -> Statement {
-        let name = self.consume(IDENTIFIER, format!("expect {:?}", kind));
-        self.consume(LEFT_PAREN, format!("expect ( after  {:?} name", kind));
+    fn function(&mut self, kind: &str) -> ParseResult<Statement> {
+        let start = self.previous().span;
+        let name = self.consume(IDENTIFIER, format!("expect {} name", kind))?;
+        self.consume(LEFT_PAREN, format!("expect '(' after {} name", kind))?;
         let mut parameters = Vec::<Token>::new();
         if !self.check(RIGHT_PAREN) {
             loop {
-                parameters.push(self.consume(IDENTIFIER, format!("expect parameter name")));
+                parameters.push(self.consume(IDENTIFIER, "expect parameter name")?);
                 if !self.expect(vec![COMMA]) {
                     break;
                 }
             }
         }
-        self.consume(RIGHT_PAREN, "expect ) after parameters");
-        self.consume(LEFT_BRACE, "expect { before function body");
+        self.consume(RIGHT_PAREN, "expect ')' after parameters")?;
+        self.consume(LEFT_BRACE, format!("expect '{{' before {} body", kind))?;
 
-        let body = self.block();
+        let body = self.block()?;
 
-        Statement::Function(name, parameters, body)
+        Ok(Statement::Function(
+            name,
+            parameters,
+            body,
+            self.span_from(start),
+        ))
     }
 
-    fn expression_statement(&mut self) -> Statement {
-        Statement::Expression(self.expression())
+    fn expression_statement(&mut self) -> ParseResult<Statement> {
+        let expr = self.expression()?;
+        self.expect(vec![SEMICOLON]);
+        Ok(Statement::Expression(expr))
     }
 
-    // expression     → equality ;
+    // expression     → assignment ;
+    // assignment     → IDENTIFIER "=" assignment
+    //                | logic_or ;
+    // logic_or       → logic_and ( "or" logic_and )* ;
+    // logic_and      → equality ( "and" equality )* ;
     // equality       → comparison ( ( "!=" | "==" ) comparison )* ;
     // comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
     // term           → factor ( ( "-" | "+" ) factor )* ;
@@ -144,130 +282,205 @@ impl Parser {
     //                | primary ;
     // primary        → NUMBER | STRING | "true" | "false" | "nil"
     //                | "(" expression ")" ;
-    fn expression(&mut self) -> Expression {
+    fn expression(&mut self) -> ParseResult<Expression> {
         self.assignment()
     }
 
-    fn equality(&mut self) -> Expression {
-        let mut expr = self.comparison();
+    fn or(&mut self) -> ParseResult<Expression> {
+        let mut expr = self.and()?;
+
+        while self.expect(vec![OR]) {
+            let op = self.previous();
+            let right = self.and()?;
+            expr = Expression::Logical(Box::new(expr), op, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> ParseResult<Expression> {
+        let mut expr = self.equality()?;
+
+        while self.expect(vec![AND]) {
+            let op = self.previous();
+            let right = self.equality()?;
+            expr = Expression::Logical(Box::new(expr), op, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> ParseResult<Expression> {
+        let mut expr = self.comparison()?;
 
         while self.expect(vec![BANG_EQUAL, EQUAL_EQUAL]) {
             let op = self.previous();
-            let cmp = self.comparison();
+            let cmp = self.comparison()?;
             expr = Expression::Binary(Box::new(expr), op, Box::new(cmp));
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn comparison(&mut self) -> Expression {
-        let mut expr = self.term();
+    fn comparison(&mut self) -> ParseResult<Expression> {
+        let mut expr = self.term()?;
 
         while self.expect(vec![GREATER, GREATER_EQUAL, LESS, LESS_EQUAL]) {
             let op = self.previous();
-            let right = self.term();
+            let right = self.term()?;
             expr = Expression::Binary(Box::new(expr), op, Box::new(right));
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn term(&mut self) -> Expression {
-        let mut expr = self.factor();
+    fn term(&mut self) -> ParseResult<Expression> {
+        let mut expr = self.factor()?;
 
         while self.expect(vec![MINUS, PLUS]) {
             let op = self.previous();
-            let right = self.factor();
+            let right = self.factor()?;
             expr = Expression::Binary(Box::new(expr), op, Box::new(right));
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn factor(&mut self) -> Expression {
-        let mut expr = self.unary();
+    fn factor(&mut self) -> ParseResult<Expression> {
+        let mut expr = self.unary()?;
 
         while self.expect(vec![SLASH, STAR]) {
             let op = self.previous();
-            let right = self.unary();
+            let right = self.unary()?;
             expr = Expression::Binary(Box::new(expr), op, Box::new(right));
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn unary(&mut self) -> Expression {
+    fn unary(&mut self) -> ParseResult<Expression> {
         if self.expect(vec![BANG, MINUS]) {
             let op = self.previous();
-            let right = self.unary();
-            return Expression::Unary(op, Box::new(right));
+            let right = self.unary()?;
+            return Ok(Expression::Unary(op, Box::new(right)));
         }
 
         self.call()
     }
 
-    fn call(&mut self) -> Expression {
-        let mut expr = self.primary();
+    fn call(&mut self) -> ParseResult<Expression> {
+        let mut expr = self.primary()?;
         loop {
             // 嵌套执行的函数形式处理
             // f1(f2())
             if self.expect(vec![LEFT_PAREN]) {
-                expr = self.finish_call(expr);
+                expr = self.finish_call(expr)?;
             } else {
                 break;
             }
         }
-        expr
+        Ok(expr)
     }
 
-    fn finish_call(&mut self, callee: Expression) -> Expression {
+    fn finish_call(&mut self, callee: Expression) -> ParseResult<Expression> {
         let mut arguments = Vec::new();
         if !self.check(RIGHT_PAREN) {
             loop {
-                arguments.push(self.expression());
+                arguments.push(self.expression()?);
                 if !self.expect(vec![COMMA]) {
                     break;
                 }
             }
         }
 
-        let paren = self.consume(RIGHT_PAREN, "expect ')' after arguments");
-        Expression::Call(Box::new(callee), paren, arguments)
+        let paren = self.consume(RIGHT_PAREN, "expect ')' after arguments")?;
+        Ok(Expression::Call(Box::new(callee), paren, arguments))
     }
 
-    fn primary(&mut self) -> Expression {
+    fn primary(&mut self) -> ParseResult<Expression> {
         if self.expect(vec![FALSE]) {
-            return Expression::Literal(Object::Bool(false));
+            return Ok(Expression::Literal(
+                Object::Bool(false),
+                self.previous().span,
+            ));
+        }
+
+        if self.expect(vec![NIL]) {
+            return Ok(Expression::Literal(Object::Nil, self.previous().span));
         }
 
         if self.expect(vec![TRUE]) {
-            return Expression::Literal(Object::Bool(true));
+            return Ok(Expression::Literal(
+                Object::Bool(true),
+                self.previous().span,
+            ));
         }
 
         if self.expect(vec![NUMBER, STRING]) {
-            return Expression::Literal(self.previous().literal);
+            let token = self.previous();
+            return Ok(Expression::Literal(token.literal, token.span));
         }
 
         if self.expect(vec![LEFT_PAREN]) {
-            let expr = self.expression();
-            self.consume(RIGHT_PAREN, "expect ')' after expression");
-            return Expression::Grouping(Box::new(expr));
+            let start = self.previous().span;
+            let expr = self.expression()?;
+            self.consume(RIGHT_PAREN, "expect ')' after expression")?;
+            return Ok(Expression::Grouping(Box::new(expr), self.span_from(start)));
         }
 
         if self.expect(vec![IDENTIFIER]) {
-            return Expression::Var(self.previous());
+            return Ok(Expression::Var(self.previous(), None));
         }
 
-        Expression::Mark
+        Err(self.error("expect expression"))
     }
 
-    /// 如果下一个 token 符合预期， 指针后移，否则抛出异常
-    fn consume<T: AsRef<str> + Display>(&mut self, tag: TokenType, message: T) -> Token {
+    /// 如果下一个 token 符合预期， 指针后移，否则返回错误
+    fn consume<T: AsRef<str> + Display>(
+        &mut self,
+        tag: TokenType,
+        message: T,
+    ) -> ParseResult<Token> {
         if self.check(tag) {
-            return self.advance();
+            return Ok(self.advance());
         }
 
-        panic!("{}", message)
+        Err(self.error(message))
+    }
+
+    /// 从 start 开始，覆盖到上一个 token 结束
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous().span)
+    }
+
+    /// 在当前 token 处构造一个错误
+    fn error<T: AsRef<str> + Display>(&self, message: T) -> ParseError {
+        ParseError::new(self.peek(), message)
+    }
+
+    /// 出错位置是 ERROR token 时，Lexer 已经报告过了，这里不再重复
+    fn report(&mut self, error: ParseError) {
+        if error.token.tag != ERROR {
+            self.errors.push(error);
+        }
+    }
+
+    /// 丢弃 token 直到下一条语句的开始
+    fn synchronize(&mut self) {
+        self.advance();
+
+        while !self.is_at_end() {
+            if self.previous().tag == SEMICOLON {
+                return;
+            }
+
+            match self.peek().tag {
+                CLASS | FUN | FUNCTION | VAR | FOR | IF | WHILE | PRINT | RETURN => return,
+                _ => {}
+            }
+
+            self.advance();
+        }
     }
 
     /// 如果找到了一个符合条件的token，同时指针后移
@@ -297,16 +510,16 @@ impl Parser {
     }
 
     fn peek(&self) -> Token {
-        self.tokens.get(self.current).unwrap().clone()
+        self.tokens[self.current].clone()
     }
 
     /// 返回上一个token，current 指针不变
     fn previous(&self) -> Token {
-        self.tokens.get(self.current - 1).unwrap().clone()
+        self.tokens[self.current.saturating_sub(1)].clone()
     }
 
-    fn is_at_end(&mut self) -> bool {
-        self.current == self.tokens.len()
+    fn is_at_end(&self) -> bool {
+        self.tokens[self.current].tag == EOF
     }
 }
 
@@ -314,12 +527,104 @@ impl Parser {
 fn test() {
     use crate::lang::lexer::Lexer;
 
-    // FIXME: Option Unwrap Error
     let mut l = Lexer::new(String::from("function a() {print 1}"));
-    l.scan_tokens();
+    let (tokens, _) = l.scan_tokens();
 
-    let mut parser = Parser::new(l.tokens);
-    let exp = parser.parse();
+    let mut parser = Parser::new(tokens);
+    let (exp, errors) = parser.parse();
 
+    assert!(errors.is_empty());
     println!("{:#?}", exp);
 }
+
+#[test]
+fn report_every_error() {
+    use crate::lang::lexer::Lexer;
+
+    let mut l = Lexer::new(String::from(
+        "var a = ;
+        print 1;
+        var = 2;
+        if (true print 3;
+        print 4;",
+    ));
+    let (tokens, _) = l.scan_tokens();
+
+    let (statements, errors) = Parser::new(tokens).parse();
+
+    assert_eq!(statements.len(), 2);
+    let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+    assert_eq!(lines, vec![1, 3, 4]);
+    assert_eq!(errors[0].token.lexeme, ";");
+    assert_eq!(errors[0].message, "expect expression");
+    assert_eq!(
+        errors[1].to_string(),
+        "[line 3] Error at '=': expect variable name"
+    );
+}
+
+#[test]
+fn node_span() {
+    use crate::lang::lexer::Lexer;
+
+    let source = "if (a) {\n  print (1 + b);\n} else c = f(2)";
+    let mut l = Lexer::new(String::from(source));
+    let (tokens, _) = l.scan_tokens();
+    let (statements, _) = Parser::new(tokens).parse();
+
+    let text = |span: Span| &source[span.offset..span.end()];
+
+    let stmt = &statements[0];
+    assert_eq!(text(stmt.span()), source);
+    if let Statement::If(_, condition, then_stmt, Some(else_stmt)) = stmt {
+        assert_eq!(text(condition.span()), "a");
+        assert_eq!(text(then_stmt.span()), "{\n  print (1 + b);\n}");
+        assert_eq!(text(else_stmt.span()), "c = f(2)");
+        if let Statement::Block(body, _) = then_stmt.as_ref() {
+            assert_eq!(text(body[0].span()), "print (1 + b)");
+            assert_eq!((body[0].span().line, body[0].span().column), (2, 3));
+        }
+    } else {
+        panic!("expect if statement");
+    }
+}
+
+#[test]
+fn skip_error_token() {
+    use crate::lang::lexer::Lexer;
+
+    let mut l = Lexer::new(String::from("var a = @;\nprint a;"));
+    let (tokens, lex_errors) = l.scan_tokens();
+    let (statements, errors) = Parser::new(tokens).parse();
+
+    assert_eq!(lex_errors.len(), 1);
+    assert!(errors.is_empty());
+    assert_eq!(statements.len(), 1);
+}
+
+#[test]
+fn error_at_end() {
+    use crate::lang::lexer::Lexer;
+
+    let mut l = Lexer::new(String::from("function a() {\n  print (1 +"));
+    let (tokens, _) = l.scan_tokens();
+    assert_eq!(tokens.last().map(|t| (t.tag, t.line)), Some((EOF, 2)));
+
+    let (_, errors) = Parser::new(tokens).parse();
+    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "[line 2] Error at end: expect expression",
+            "[line 2] Error at end: expect '}' after block",
+        ]
+    );
+
+    // 任意截断的输入都不应该越界
+    let source = "if (a) { var b = f(1, (2)); } else print -c;";
+    for end in 0..=source.len() {
+        let (tokens, _) = Lexer::new(source[..end].to_string()).scan_tokens();
+        Parser::new(tokens).parse();
+    }
+    Parser::new(vec![]).parse();
+}
@@ -0,0 +1,603 @@
+// 把 AST 还原为规范格式的 Lox 源码。
+//
+// 括号只在优先级需要时添加；AST 中的 Grouping 来自源码中显式的括号，原样输出，
+// 这样重新解析得到的 AST 与原来的相同。for 循环在解析时已经转换为 while，按 while 输出。
+
+use std::fmt::Write;
+
+use super::{
+    ast::{Expression, Statement},
+    token::{Object, TokenType::*},
+    visitor::{ExprVisitor, StmtVisitor},
+};
+
+/// 运算符优先级，从低到高
+const ASSIGNMENT: u8 = 1;
+const LOGIC_OR: u8 = 2;
+const LOGIC_AND: u8 = 3;
+const EQUALITY: u8 = 4;
+const COMPARISON: u8 = 5;
+const TERM: u8 = 6;
+const FACTOR: u8 = 7;
+const UNARY: u8 = 8;
+const CALL: u8 = 9;
+const PRIMARY: u8 = 10;
+
+fn precedence(expr: &Expression) -> u8 {
+    match expr {
+        Expression::Assignment(..) => ASSIGNMENT,
+        Expression::Logical(_, operator, _) if operator.tag == OR => LOGIC_OR,
+        Expression::Logical(..) => LOGIC_AND,
+        Expression::Binary(_, operator, _) => match operator.tag {
+            EQUAL_EQUAL | BANG_EQUAL => EQUALITY,
+            GREATER | GREATER_EQUAL | LESS | LESS_EQUAL => COMPARISON,
+            PLUS | MINUS => TERM,
+            _ => FACTOR,
+        },
+        Expression::Unary(..) => UNARY,
+        Expression::Call(..) => CALL,
+        Expression::Literal(..) | Expression::Grouping(..) | Expression::Var(..) => PRIMARY,
+        Expression::Mark => PRIMARY,
+    }
+}
+
+/// 语句是否以不带 else 的 if 结尾，后面紧跟的 else 会被解析到这个 if 上
+fn ends_in_if_without_else(stmt: &Statement) -> bool {
+    match stmt {
+        Statement::If(_, _, _, None) => true,
+        Statement::If(_, _, _, Some(else_stmt)) => ends_in_if_without_else(else_stmt),
+        Statement::While(_, _, body) => ends_in_if_without_else(body),
+        _ => false,
+    }
+}
+
+pub struct Printer {
+    indent: String,
+    level: usize,
+    /// 当前位置允许的最低优先级，低于它的表达式需要加括号
+    min: u8,
+    out: String,
+}
+
+impl Printer {
+    /// 默认使用 4 个空格缩进
+    pub fn new() -> Self {
+        Printer {
+            indent: "    ".to_string(),
+            level: 0,
+            min: ASSIGNMENT,
+            out: String::new(),
+        }
+    }
+
+    pub fn print(&mut self, statements: &[Statement]) -> String {
+        self.visit_stmts(statements);
+        std::mem::take(&mut self.out)
+    }
+
+    fn expr_at(&mut self, expr: &Expression, min: u8) {
+        let outer = std::mem::replace(&mut self.min, min);
+        self.visit_expr(expr);
+        self.min = outer;
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.level {
+            self.out.push_str(&self.indent);
+        }
+    }
+
+    /// if / while 的主体：块跟在同一行，其余语句换行缩进
+    fn body(&mut self, stmt: &Statement) {
+        if let Statement::Block(..) = stmt {
+            self.out.push(' ');
+            self.visit_stmt(stmt);
+        } else {
+            self.out.push('\n');
+            self.level += 1;
+            self.write_indent();
+            self.visit_stmt(stmt);
+            self.level -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+impl Printer {
+    fn with_indent<T: Into<String>>(mut self, indent: T) -> Self {
+        self.indent = indent.into();
+        self
+    }
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'ast> StmtVisitor<'ast> for Printer {
+    type StmtOutput = ();
+
+    fn visit_stmts(&mut self, statements: &'ast [Statement]) {
+        for stmt in statements {
+            self.write_indent();
+            self.visit_stmt(stmt);
+            self.out.push('\n');
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Statement) {
+        match stmt {
+            Statement::Print(expr, _) => {
+                self.out.push_str("print ");
+                self.expr_at(expr, ASSIGNMENT);
+                self.out.push(';');
+            }
+            Statement::Expression(expr) => {
+                self.expr_at(expr, ASSIGNMENT);
+                self.out.push(';');
+            }
+            Statement::Var(name, initializer, _) => {
+                let _ = write!(self.out, "var {}", name.lexeme);
+                if let Some(initializer) = initializer {
+                    self.out.push_str(" = ");
+                    self.expr_at(initializer, ASSIGNMENT);
+                }
+                self.out.push(';');
+            }
+            Statement::Block(statements, _) => {
+                if statements.is_empty() {
+                    self.out.push_str("{}");
+                    return;
+                }
+                self.out.push_str("{\n");
+                self.level += 1;
+                self.visit_stmts(statements);
+                self.level -= 1;
+                self.write_indent();
+                self.out.push('}');
+            }
+            Statement::If(_, condition, then_stmt, else_stmt) => {
+                self.out.push_str("if (");
+                self.expr_at(condition, ASSIGNMENT);
+                self.out.push(')');
+                // 避免 else 被解析到内层的 if 上，只能补一个块
+                let then_stmt = if else_stmt.is_some() && ends_in_if_without_else(then_stmt) {
+                    Statement::Block(vec![*then_stmt.clone()], Default::default())
+                } else {
+                    *then_stmt.clone()
+                };
+                self.body(&then_stmt);
+                if let Some(else_stmt) = else_stmt {
+                    if let Statement::Block(..) = then_stmt {
+                        self.out.push(' ');
+                    } else {
+                        self.out.push('\n');
+                        self.write_indent();
+                    }
+                    self.out.push_str("else");
+                    if let Statement::If(..) = else_stmt.as_ref() {
+                        self.out.push(' ');
+                        self.visit_stmt(else_stmt);
+                    } else {
+                        self.body(else_stmt);
+                    }
+                }
+            }
+            Statement::Function(name, parameters, body, _) => {
+                let parameters: Vec<&str> = parameters.iter().map(|p| p.lexeme.as_str()).collect();
+                let _ = write!(self.out, "fun {}({}) ", name.lexeme, parameters.join(", "));
+                // 函数体与块的格式相同
+                self.visit_stmt(&Statement::Block(body.clone(), Default::default()));
+            }
+            Statement::Return(_, value) => {
+                self.out.push_str("return");
+                if let Some(value) = value {
+                    self.out.push(' ');
+                    self.expr_at(value, ASSIGNMENT);
+                }
+                self.out.push(';');
+            }
+            Statement::While(_, condition, body) => {
+                self.out.push_str("while (");
+                self.expr_at(condition, ASSIGNMENT);
+                self.out.push(')');
+                self.body(body);
+            }
+        }
+    }
+}
+
+impl<'ast> ExprVisitor<'ast> for Printer {
+    type Output = ();
+
+    fn visit_expr(&mut self, expr: &'ast Expression) {
+        let prec = precedence(expr);
+        let wrap = prec < self.min;
+        if wrap {
+            self.out.push('(');
+        }
+
+        match expr {
+            Expression::Literal(object, _) => match object {
+                Object::String(v) => {
+                    let _ = write!(self.out, "\"{}\"", v);
+                }
+                object => {
+                    let _ = write!(self.out, "{}", object);
+                }
+            },
+            Expression::Assignment(name, value, _) => {
+                let _ = write!(self.out, "{} = ", name.lexeme);
+                // 赋值是右结合的
+                self.expr_at(value, ASSIGNMENT);
+            }
+            Expression::Unary(operator, right) => {
+                self.out.push_str(&operator.lexeme);
+                self.expr_at(right, UNARY);
+            }
+            Expression::Binary(left, operator, right)
+            | Expression::Logical(left, operator, right) => {
+                // 左结合：右侧同级的表达式需要括号
+                self.expr_at(left, prec);
+                let _ = write!(self.out, " {} ", operator.lexeme);
+                self.expr_at(right, prec + 1);
+            }
+            Expression::Grouping(inner, _) => {
+                self.out.push('(');
+                self.expr_at(inner, ASSIGNMENT);
+                self.out.push(')');
+            }
+            Expression::Call(callee, _, arguments) => {
+                self.expr_at(callee, CALL);
+                self.out.push('(');
+                for (i, argument) in arguments.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr_at(argument, ASSIGNMENT);
+                }
+                self.out.push(')');
+            }
+            Expression::Var(name, _) => self.out.push_str(&name.lexeme),
+            // 解析器不会产生 Mark
+            Expression::Mark => {}
+        }
+
+        if wrap {
+            self.out.push(')');
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse(source: &str) -> Vec<Statement> {
+    use super::{lexer::Lexer, parser::Parser};
+
+    let (tokens, _) = Lexer::new(String::from(source)).scan_tokens();
+    let (statements, errors) = Parser::new(tokens).parse();
+    assert!(errors.is_empty(), "{:?}\n{}", errors, source);
+    statements
+}
+
+/// 去掉位置信息，只比较结构
+#[cfg(test)]
+fn erase(statements: &mut [Statement]) {
+    use super::{
+        token::{Span, Token},
+        visitor::{walk_expr_mut, walk_stmt_mut, ExprVisitorMut, StmtVisitorMut},
+    };
+
+    struct Erase;
+
+    fn token(token: &mut Token) {
+        token.line = 0;
+        token.span = Span::default();
+    }
+
+    impl ExprVisitorMut for Erase {
+        type Output = ();
+
+        fn visit_expr_mut(&mut self, expr: &mut Expression) {
+            match expr {
+                Expression::Literal(_, span) | Expression::Grouping(_, span) => {
+                    *span = Span::default()
+                }
+                Expression::Assignment(name, ..) | Expression::Var(name, _) => token(name),
+                Expression::Unary(operator, _)
+                | Expression::Binary(_, operator, _)
+                | Expression::Logical(_, operator, _)
+                | Expression::Call(_, operator, _) => token(operator),
+                Expression::Mark => {}
+            }
+            walk_expr_mut(self, expr);
+        }
+    }
+
+    impl StmtVisitorMut for Erase {
+        type StmtOutput = ();
+
+        fn visit_stmt_mut(&mut self, stmt: &mut Statement) {
+            match stmt {
+                Statement::Print(_, span) | Statement::Block(_, span) => *span = Span::default(),
+                Statement::Var(name, _, span) => {
+                    token(name);
+                    *span = Span::default();
+                }
+                Statement::Function(name, parameters, _, span) => {
+                    token(name);
+                    parameters.iter_mut().for_each(token);
+                    *span = Span::default();
+                }
+                Statement::If(keyword, ..)
+                | Statement::Return(keyword, _)
+                | Statement::While(keyword, ..) => token(keyword),
+                Statement::Expression(_) => {}
+            }
+            walk_stmt_mut(self, stmt);
+        }
+    }
+
+    Erase.visit_stmts_mut(statements);
+}
+
+#[test]
+fn print_source() {
+    let source = "
+        var a = (1 + 2) * 3 - -4;
+        fun add(a, b) { return a + b; }
+        if (a and !(b or c)) print add(a, b)(c); else if (d) { x = y = 1 - (2 - 3); } else print \"s\";
+        for (var i = 0; i < 10; i = i + 1) print i;
+        while (true) {}
+    ";
+
+    assert_eq!(
+        Printer::new().with_indent("  ").print(&parse(source)),
+        "var a = (1 + 2) * 3 - -4;
+fun add(a, b) {
+  return a + b;
+}
+if (a and !(b or c))
+  print add(a, b)(c);
+else if (d) {
+  x = y = 1 - (2 - 3);
+} else
+  print \"s\";
+{
+  var i = 0;
+  while (i < 10) {
+    print i;
+    i = i + 1;
+  }
+}
+while (true) {}
+"
+    );
+}
+
+/// 随机生成 AST，打印后重新解析应当得到相同的结构
+#[test]
+fn round_trip() {
+    use super::token::{Token, TokenType};
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            // xorshift64
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        fn token(&mut self, tags: &[(TokenType, &str)]) -> Token {
+            let (tag, lexeme) = tags[self.below(tags.len())];
+            Token::new(tag, lexeme, Object::Placeholder, 0)
+        }
+
+        fn name(&mut self) -> Token {
+            self.token(&[(IDENTIFIER, "a"), (IDENTIFIER, "b"), (IDENTIFIER, "foo")])
+        }
+
+        /// 生成的表达式与解析器的输出形状一致：优先级不够的位置用 Grouping 包裹
+        fn expr(&mut self, depth: usize) -> Expression {
+            let boxed = |e| Box::new(e);
+            let kind = if depth == 0 {
+                self.below(2)
+            } else {
+                self.below(8)
+            };
+            match kind {
+                0 => Expression::Literal(
+                    match self.below(5) {
+                        0 => Object::Digit([0.0, 1.5, 42.0, 0.125][self.below(4)]),
+                        1 => Object::String(["", "str", "a b"][self.below(3)].to_string()),
+                        2 => Object::Bool(true),
+                        3 => Object::Bool(false),
+                        _ => Object::Nil,
+                    },
+                    Default::default(),
+                ),
+                1 => Expression::Var(self.name(), None),
+                2 => {
+                    let operator = self.token(&[(MINUS, "-"), (BANG, "!")]);
+                    Expression::Unary(operator, boxed(self.operand(depth, UNARY)))
+                }
+                3 | 4 => {
+                    let operator = self.token(&[
+                        (PLUS, "+"),
+                        (MINUS, "-"),
+                        (STAR, "*"),
+                        (SLASH, "/"),
+                        (LESS, "<"),
+                        (GREATER_EQUAL, ">="),
+                        (EQUAL_EQUAL, "=="),
+                        (BANG_EQUAL, "!="),
+                    ]);
+                    let prec = precedence(&Expression::Binary(
+                        boxed(Expression::Mark),
+                        operator.clone(),
+                        boxed(Expression::Mark),
+                    ));
+                    let left = self.operand(depth, prec);
+                    let right = self.operand(depth, prec + 1);
+                    Expression::Binary(boxed(left), operator, boxed(right))
+                }
+                5 => {
+                    let operator = self.token(&[(AND, "and"), (OR, "or")]);
+                    let prec = if operator.tag == OR {
+                        LOGIC_OR
+                    } else {
+                        LOGIC_AND
+                    };
+                    let left = self.operand(depth, prec);
+                    let right = self.operand(depth, prec + 1);
+                    Expression::Logical(boxed(left), operator, boxed(right))
+                }
+                6 => {
+                    let callee = self.operand(depth, CALL);
+                    let arguments = (0..self.below(3)).map(|_| self.expr(depth - 1)).collect();
+                    let paren = Token::new(RIGHT_PAREN, ")", Object::Placeholder, 0);
+                    Expression::Call(boxed(callee), paren, arguments)
+                }
+                _ => Expression::Assignment(self.name(), boxed(self.expr(depth - 1)), None),
+            }
+        }
+
+        fn operand(&mut self, depth: usize, min: u8) -> Expression {
+            let expr = self.expr(depth - 1);
+            if precedence(&expr) < min {
+                Expression::Grouping(Box::new(expr), Default::default())
+            } else {
+                expr
+            }
+        }
+
+        fn stmt(&mut self, depth: usize) -> Statement {
+            let keyword = |tag, lexeme| Token::new(tag, lexeme, Object::Placeholder, 0);
+            let kind = if depth == 0 {
+                self.below(4)
+            } else {
+                self.below(8)
+            };
+            match kind {
+                0 => Statement::Print(self.expr(2), Default::default()),
+                1 => Statement::Expression(self.expr(3)),
+                2 => {
+                    let initializer = if self.below(2) == 0 {
+                        Some(self.expr(2))
+                    } else {
+                        None
+                    };
+                    Statement::Var(self.name(), initializer, Default::default())
+                }
+                3 => {
+                    let value = if self.below(2) == 0 {
+                        Some(self.expr(2))
+                    } else {
+                        None
+                    };
+                    Statement::Return(keyword(RETURN, "return"), value)
+                }
+                4 => Statement::Block(self.stmts(depth - 1), Default::default()),
+                5 => {
+                    let then_stmt = self.body(depth - 1);
+                    let else_stmt = match self.below(2) {
+                        0 => Some(Box::new(self.body(depth - 1))),
+                        _ => None,
+                    };
+                    // 解析器不会产生 else 之前是不带 else 的 if 的结构
+                    let then_stmt = if else_stmt.is_some() && ends_in_if_without_else(&then_stmt) {
+                        Statement::Block(vec![then_stmt], Default::default())
+                    } else {
+                        then_stmt
+                    };
+                    Statement::If(
+                        keyword(IF, "if"),
+                        self.expr(2),
+                        Box::new(then_stmt),
+                        else_stmt,
+                    )
+                }
+                6 => {
+                    let parameters = (0..self.below(3)).map(|_| self.name()).collect();
+                    Statement::Function(
+                        self.name(),
+                        parameters,
+                        self.stmts(depth - 1),
+                        Default::default(),
+                    )
+                }
+                _ => Statement::While(
+                    keyword(WHILE, "while"),
+                    self.expr(2),
+                    Box::new(self.body(depth - 1)),
+                ),
+            }
+        }
+
+        /// if / while 的主体不能是声明
+        fn body(&mut self, depth: usize) -> Statement {
+            loop {
+                match self.stmt(depth) {
+                    Statement::Var(..) | Statement::Function(..) => continue,
+                    stmt => return stmt,
+                }
+            }
+        }
+
+        fn stmts(&mut self, depth: usize) -> Vec<Statement> {
+            (0..self.below(4)).map(|_| self.stmt(depth)).collect()
+        }
+    }
+
+    // else 之前是以不带 else 的 if 结尾的 while，打印时需要补一个块
+    let keyword = |tag, lexeme| Token::new(tag, lexeme, Object::Placeholder, 0);
+    let var = |name| Expression::Var(keyword(IDENTIFIER, name), None);
+    let print = |value: f32| {
+        Box::new(Statement::Print(
+            Expression::Literal(Object::Digit(value), Default::default()),
+            Default::default(),
+        ))
+    };
+    let inner = Statement::While(
+        keyword(WHILE, "while"),
+        var("b"),
+        Box::new(Statement::If(keyword(IF, "if"), var("c"), print(1.0), None)),
+    );
+    let statement = |then_stmt| {
+        Statement::If(
+            keyword(IF, "if"),
+            var("a"),
+            Box::new(then_stmt),
+            Some(print(2.0)),
+        )
+    };
+    let source = Printer::new().print(&[statement(inner.clone())]);
+    let mut parsed = parse(&source);
+    erase(&mut parsed);
+    assert_eq!(
+        parsed,
+        vec![statement(Statement::Block(vec![inner], Default::default()))],
+        "{}",
+        source
+    );
+
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..500 {
+        let statements = rng.stmts(3);
+        for indent in &["    ", "\t"] {
+            let source = Printer::new().with_indent(*indent).print(&statements);
+            let mut parsed = parse(&source);
+            erase(&mut parsed);
+            assert_eq!(parsed, statements, "{}", source);
+        }
+    }
+
+    // 解析器产生的 AST（包括 for 循环转换成的 while）打印结果是稳定的
+    let source = "for (var i = 0; i < 3; i = i + 1) { if (i == 1) print (i); }";
+    let printed = Printer::new().print(&parse(source));
+    assert_eq!(Printer::new().print(&parse(&printed)), printed);
+}
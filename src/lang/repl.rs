@@ -0,0 +1,149 @@
+// 交互式解释器的核心部分，读取输入的循环在 main.rs 中。
+//
+// 整个会话共用一个 Engine，之前定义的变量和函数一直有效。
+// 单独的表达式语句会输出它的值；出错时丢弃本次输入，不影响已有的定义。
+
+use super::{
+    diagnostic::Diagnostic, engine::Engine, error::LexErrorKind, lexer::Lexer, token::Keywords,
+    token::TokenType::*,
+};
+
+pub struct Repl {
+    engine: Engine,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Repl {
+            engine: Engine::new(),
+        }
+    }
+
+    /// 执行一段输入，返回其中表达式语句的值
+    pub fn eval(&mut self, source: &str) -> Result<Vec<String>, Vec<Diagnostic>> {
+        match self.engine.execute(source) {
+            Ok(values) => Ok(values.iter().map(|value| value.to_string()).collect()),
+            Err(error) => Err(error.diagnostics().to_vec()),
+        }
+    }
+
+    /// 当前定义的全局变量及其值
+    pub fn globals(&self) -> Vec<(String, String)> {
+        self.engine
+            .globals()
+            .into_iter()
+            .map(|(name, value)| (name, value.to_string()))
+            .collect()
+    }
+
+    /// 可以补全的名字：关键字以及全局变量
+    pub fn completions(&self) -> Vec<String> {
+        let mut names: Vec<String> = Keywords.keys().map(|k| k.to_string()).collect();
+        names.extend(self.engine.globals().into_iter().map(|(name, _)| name));
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// 丢弃全部定义，回到刚启动时的状态
+    pub fn reset(&mut self) {
+        *self = Repl::new();
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 括号或字符串没有闭合，需要继续读取下一行
+pub fn is_incomplete(source: &str) -> bool {
+    let (tokens, errors) = Lexer::new(source.to_string()).scan_tokens();
+    if errors
+        .iter()
+        .any(|e| e.kind == LexErrorKind::UnterminatedString)
+    {
+        return true;
+    }
+    let mut depth = 0;
+    for token in &tokens {
+        match token.tag {
+            LEFT_PAREN | LEFT_BRACE => depth += 1,
+            RIGHT_PAREN | RIGHT_BRACE => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+/// 光标所在的名字的起始位置，补全时用它之后到光标的部分作为前缀
+pub fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !(c.is_ascii_alphanumeric() || c == '_'))
+        .map_or(0, |(i, c)| i + c.len_utf8())
+}
+
+#[test]
+fn session() {
+    let mut repl = Repl::new();
+    assert_eq!(repl.eval("var a = 1;").unwrap(), Vec::<String>::new());
+    assert_eq!(
+        repl.eval("fun add(x, y) { return x + y; }").unwrap().len(),
+        0
+    );
+    assert_eq!(repl.eval("add(a, 2)").unwrap(), vec!["3"]);
+    assert_eq!(repl.eval("a = a + 1; a * 10").unwrap(), vec!["2", "20"]);
+
+    // 出错之后之前的定义仍然有效
+    assert!(repl.eval("var b = ;").is_err());
+    assert!(repl.eval("var c = missing;").is_err());
+    assert!(repl.eval("c").is_err());
+    assert!(repl.eval("a + \"s\"").is_err());
+    assert!(repl.eval("{ var d = 1; d + nil; }").is_err());
+    assert_eq!(
+        repl.eval("{ var d = a; print d; } add(a, a)").unwrap(),
+        vec!["4"]
+    );
+}
+
+#[test]
+fn globals() {
+    let mut repl = Repl::new();
+    repl.eval("var answer = 42; fun area(r) { return r * r; }")
+        .unwrap();
+    let globals = repl.globals();
+    assert_eq!(globals[0], ("answer".to_string(), "42".to_string()));
+    assert_eq!(globals[1], ("area".to_string(), "<fn area>".to_string()));
+
+    let completions = repl.completions();
+    assert!(completions.contains(&"answer".to_string()));
+    assert!(completions.contains(&"while".to_string()));
+
+    repl.reset();
+    // 只剩下内置函数
+    assert_eq!(repl.globals()[0].0, "clock");
+    assert_eq!(repl.globals().len(), 1);
+    assert!(repl.eval("answer").is_err());
+}
+
+#[test]
+fn incomplete() {
+    assert!(is_incomplete("fun f() {"));
+    assert!(is_incomplete("print (1 +"));
+    assert!(is_incomplete("print \"abc"));
+    assert!(!is_incomplete("fun f() {}"));
+    assert!(!is_incomplete("print 1)"));
+}
+
+#[test]
+fn completion_prefix() {
+    assert_eq!(word_start("print ans", 9), 6);
+    assert_eq!(word_start("my_var", 6), 0);
+    assert_eq!(word_start("", 0), 0);
+    // 非 ASCII 字符不能从中间截断
+    assert_eq!(word_start("print é", "print é".len()), "print é".len());
+    assert_eq!(word_start("\"é\" + ab", "\"é\" + ab".len()), 7);
+}
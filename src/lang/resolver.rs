@@ -0,0 +1,410 @@
+// http://www.craftinginterpreters.com/resolving-and-binding.html
+//
+// 在执行之前静态地计算每个局部变量位于第几层作用域，
+// 结果直接写回 Expression::Var / Expression::Assignment 中。
+// 同时检查未声明的变量、重复声明、顶层 return 以及调用已知函数时的参数个数。
+
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    ast::{Expression, Statement},
+    error::SemanticError,
+    token::Token,
+    visitor::{walk_expr_mut, walk_stmt_mut, ExprVisitorMut, StmtVisitorMut},
+};
+
+/// 局部作用域中的一个名字
+#[derive(Debug, Clone, Copy)]
+struct Binding {
+    /// 是否已经完成初始化
+    defined: bool,
+    /// 由 fun 声明时记录参数个数，其余为 None
+    arity: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+}
+
+/// 参数个数的检查推迟到最后，确认函数名没有被重新赋值
+#[derive(Clone)]
+struct PendingCall {
+    name: Token,
+    expected: usize,
+    got: usize,
+}
+
+#[derive(Clone)]
+pub struct Resolver {
+    /// 局部作用域栈。全局作用域不在其中
+    scopes: Vec<HashMap<String, Binding>>,
+    /// 已知的全局变量，跨多次 resolve 保留，值同 Binding::arity
+    globals: HashMap<String, Option<usize>>,
+    /// 本次 resolve 中尚未执行到的顶层声明，只有函数体可以提前引用
+    hoisted: HashMap<String, Option<usize>>,
+    /// 出现过赋值的名字，这些名字上的函数不再视为已知
+    assigned: HashSet<String>,
+    calls: Vec<PendingCall>,
+    function: FunctionType,
+    errors: Vec<SemanticError>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            scopes: vec![],
+            globals: HashMap::new(),
+            hoisted: HashMap::new(),
+            assigned: HashSet::new(),
+            calls: vec![],
+            function: FunctionType::None,
+            errors: vec![],
+        }
+    }
+
+    /// 声明由宿主程序定义的全局变量，arity 为已知函数的参数个数
+    pub fn declare_global(&mut self, name: &str, arity: Option<usize>) {
+        self.globals.insert(name.to_string(), arity);
+    }
+
+    pub fn resolve(&mut self, statements: &mut [Statement]) -> Vec<SemanticError> {
+        // 全局变量可以在声明之前被函数引用，先收集顶层的全部声明
+        for stmt in statements.iter() {
+            let (name, arity) = match stmt {
+                Statement::Var(name, _, _) => (name, None),
+                Statement::Function(name, parameters, _, _) => (name, Some(parameters.len())),
+                _ => continue,
+            };
+            // 重复声明的名字不再视为已知函数
+            let known =
+                self.globals.contains_key(&name.lexeme) || self.hoisted.contains_key(&name.lexeme);
+            self.hoisted
+                .insert(name.lexeme.clone(), if known { None } else { arity });
+        }
+
+        self.visit_stmts_mut(statements);
+        self.hoisted.clear();
+
+        for call in std::mem::take(&mut self.calls) {
+            if !self.assigned.contains(&call.name.lexeme) {
+                self.error(
+                    &call.name,
+                    format!("Expected {} arguments but got {}", call.expected, call.got),
+                );
+            }
+        }
+
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.sort_by_key(|e| e.token.span.offset);
+        errors
+    }
+
+    /// 参数和函数体共用同一个作用域，与调用时创建的 Environment 对应
+    fn resolve_function(&mut self, parameters: &[Token], body: &mut [Statement]) {
+        let enclosing = std::mem::replace(&mut self.function, FunctionType::Function);
+        self.begin_scope();
+        for (i, param) in parameters.iter().enumerate() {
+            if parameters[..i].iter().any(|p| p.lexeme == param.lexeme) {
+                self.error(param, format!("Duplicate parameter '{}'", param.lexeme));
+            } else {
+                self.declare(param);
+            }
+            self.define(param);
+        }
+        self.visit_stmts_mut(body);
+        self.end_scope();
+        self.function = enclosing;
+    }
+
+    /// 从内向外查找，返回变量所在作用域与当前作用域的距离。
+    /// 找不到时视为全局变量，全局变量也没有声明则报错。
+    /// 顶层代码按源码顺序执行，不能使用后面才声明的全局变量
+    fn resolve_local(&mut self, name: &Token) -> Option<usize> {
+        let depth = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name.lexeme));
+        if depth.is_none() && !self.globals.contains_key(&name.lexeme) {
+            if !self.hoisted.contains_key(&name.lexeme) {
+                self.error(name, format!("Undefined variable '{}'", name.lexeme));
+            } else if self.function == FunctionType::None {
+                self.error(
+                    name,
+                    format!("'{}' is used before its declaration", name.lexeme),
+                );
+            }
+        }
+        depth
+    }
+
+    /// 执行到顶层声明时才把它加入全局变量
+    fn declare_hoisted(&mut self, name: &Token) {
+        if self.scopes.is_empty() {
+            let arity = self.hoisted.remove(&name.lexeme).flatten();
+            self.globals.insert(name.lexeme.clone(), arity);
+        }
+    }
+
+    /// 名字绑定到 fun 声明时返回参数个数
+    fn arity(&self, name: &Token, depth: Option<usize>) -> Option<usize> {
+        match depth {
+            Some(depth) => self.scopes[self.scopes.len() - 1 - depth][&name.lexeme].arity,
+            None => self
+                .globals
+                .get(&name.lexeme)
+                .or_else(|| self.hoisted.get(&name.lexeme))
+                .copied()
+                .flatten(),
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let binding = Binding {
+                defined: false,
+                arity: None,
+            };
+            if scope.insert(name.lexeme.clone(), binding).is_some() {
+                self.error(
+                    name,
+                    format!("'{}' is already declared in this scope", name.lexeme),
+                );
+            }
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(binding) = self.scopes.last_mut().and_then(|s| s.get_mut(&name.lexeme)) {
+            binding.defined = true;
+        }
+    }
+
+    fn error<T: AsRef<str> + std::fmt::Display>(&mut self, token: &Token, message: T) {
+        self.errors.push(SemanticError::new(token.clone(), message));
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StmtVisitorMut for Resolver {
+    type StmtOutput = ();
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Statement) {
+        match stmt {
+            Statement::Block(statements, _) => {
+                self.begin_scope();
+                self.visit_stmts_mut(statements);
+                self.end_scope();
+            }
+            Statement::Var(name, initializer, _) => {
+                self.declare_hoisted(name);
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.visit_expr_mut(initializer);
+                }
+                self.define(name);
+            }
+            Statement::Function(name, parameters, body, _) => {
+                // 先定义函数名，函数体中可以递归调用自身
+                self.declare_hoisted(name);
+                self.declare(name);
+                self.define(name);
+                if let Some(binding) = self.scopes.last_mut().and_then(|s| s.get_mut(&name.lexeme))
+                {
+                    binding.arity = Some(parameters.len());
+                }
+                self.resolve_function(parameters, body);
+            }
+            Statement::Return(keyword, _) => {
+                if self.function == FunctionType::None {
+                    self.error(keyword, "Can't return from top-level code");
+                }
+                walk_stmt_mut(self, stmt);
+            }
+            _ => walk_stmt_mut(self, stmt),
+        }
+    }
+}
+
+impl ExprVisitorMut for Resolver {
+    type Output = ();
+
+    fn visit_expr_mut(&mut self, expr: &mut Expression) {
+        match expr {
+            Expression::Var(name, depth) => {
+                if let Some(false) = self
+                    .scopes
+                    .last()
+                    .and_then(|s| s.get(&name.lexeme))
+                    .map(|b| b.defined)
+                {
+                    self.error(name, "Can't read local variable in its own initializer");
+                }
+                *depth = self.resolve_local(name);
+            }
+            Expression::Assignment(name, value, depth) => {
+                self.visit_expr_mut(value);
+                self.assigned.insert(name.lexeme.clone());
+                *depth = self.resolve_local(name);
+            }
+            Expression::Call(callee, _, arguments) => {
+                self.visit_expr_mut(callee);
+                if let Expression::Var(name, depth) = callee.as_ref() {
+                    if let Some(expected) = self.arity(name, *depth) {
+                        if expected != arguments.len() {
+                            self.calls.push(PendingCall {
+                                name: name.clone(),
+                                expected,
+                                got: arguments.len(),
+                            });
+                        }
+                    }
+                }
+                for argument in arguments.iter_mut() {
+                    self.visit_expr_mut(argument);
+                }
+            }
+            _ => walk_expr_mut(self, expr),
+        }
+    }
+}
+
+#[test]
+fn resolve_depth() {
+    use super::{lexer::Lexer, parser::Parser};
+
+    let (tokens, _) = Lexer::new(String::from(
+        "var g = 1;
+        fun f(a) {
+            { a = g; }
+            return a;
+        }",
+    ))
+    .scan_tokens();
+    let (mut statements, _) = Parser::new(tokens).parse();
+    let errors = Resolver::new().resolve(&mut statements);
+    assert!(errors.is_empty());
+
+    if let Statement::Function(_, _, body, _) = &statements[1] {
+        match (&body[0], &body[1]) {
+            (Statement::Block(block, _), Statement::Return(_, Some(Expression::Var(_, depth)))) => {
+                assert_eq!(*depth, Some(0));
+                if let Statement::Expression(Expression::Assignment(_, value, depth)) = &block[0] {
+                    assert_eq!(*depth, Some(1));
+                    assert!(matches!(value.as_ref(), Expression::Var(_, None)));
+                } else {
+                    panic!("expect assignment");
+                }
+            }
+            _ => panic!("unexpected function body"),
+        }
+    } else {
+        panic!("expect function");
+    }
+}
+
+#[test]
+fn own_initializer() {
+    use super::{lexer::Lexer, parser::Parser};
+
+    let (tokens, _) =
+        Lexer::new(String::from("var a = 1;\n{\n  var a = a;\n}\nvar b = b;")).scan_tokens();
+    let (mut statements, _) = Parser::new(tokens).parse();
+    let errors = Resolver::new().resolve(&mut statements);
+
+    // 全局变量不受限制
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].to_string(),
+        "[line 3] Error at 'a': Can't read local variable in its own initializer"
+    );
+}
+
+#[cfg(test)]
+fn check(source: &str) -> Vec<String> {
+    use super::{lexer::Lexer, parser::Parser};
+
+    let (tokens, _) = Lexer::new(String::from(source)).scan_tokens();
+    let (mut statements, errors) = Parser::new(tokens).parse();
+    assert!(errors.is_empty(), "{:?}", errors);
+    Resolver::new()
+        .resolve(&mut statements)
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[test]
+fn static_checks() {
+    assert_eq!(
+        check(
+            "fun f(a, b, a) {
+                var c = 1;
+                var c = 2;
+                print d;
+            }
+            f(1, 2);
+            return 1;
+            e = 3;"
+        ),
+        vec![
+            "[line 1] Error at 'a': Duplicate parameter 'a'",
+            "[line 3] Error at 'c': 'c' is already declared in this scope",
+            "[line 4] Error at 'd': Undefined variable 'd'",
+            "[line 6] Error at 'f': Expected 3 arguments but got 2",
+            "[line 7] Error at 'return': Can't return from top-level code",
+            "[line 8] Error at 'e': Undefined variable 'e'",
+        ]
+    );
+
+    // 解释器在运行时报告的错误，静态检查能提前发现
+    assert_eq!(
+        check("var a = 1;\n{\n  print foo;\n}\nfunction f(a) { print a; }\nf(1, 2);"),
+        vec![
+            "[line 3] Error at 'foo': Undefined variable 'foo'",
+            "[line 6] Error at 'f': Expected 1 arguments but got 2",
+        ]
+    );
+
+    // 顶层代码按顺序执行，不能提前使用后面的全局变量
+    assert_eq!(
+        check("print x;\n{ x = 1; }\nvar x;\nprint x;"),
+        vec![
+            "[line 1] Error at 'x': 'x' is used before its declaration",
+            "[line 2] Error at 'x': 'x' is used before its declaration",
+        ]
+    );
+
+    // 全局变量可以重复声明，也可以在函数中先使用后声明；
+    // 重新赋值过的函数名不再检查参数个数
+    assert!(check(
+        "var a = 1;
+        var a = 2;
+        fun f() { return b; }
+        var b = f();
+        fun g(x) { return x; }
+        {
+            fun h(x) { return x; }
+            var k = h;
+            k(1, 2);
+        }
+        g = f;
+        g();"
+    )
+    .is_empty());
+}
@@ -1,6 +1,8 @@
-use super::ast::Statement;
+use super::{ast::Statement, environment::Environment};
 
 use std::{
+    cell::RefCell,
+    convert::TryFrom,
     fmt::{Debug, Display},
     rc::Rc,
 };
@@ -8,6 +10,7 @@ use std::{
 use phf::phf_map;
 
 #[allow(non_camel_case_types)]
+#[allow(clippy::upper_case_acronyms)]
 #[allow(unused)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenType {
@@ -58,6 +61,8 @@ pub enum TokenType {
     WHILE,
     FUNCTION,
 
+    /// 词法错误，错误本身由 Lexer 单独记录
+    ERROR,
     EOF,
 }
 
@@ -66,6 +71,7 @@ pub enum Object {
     String(String),
     Digit(f32),
     Bool(bool),
+    Nil,
 
     // 占位符
     Placeholder,
@@ -78,7 +84,96 @@ pub enum UnionObject<'a> {
     /// 引用类型
     Reference(&'a Object),
 
-    Function(Rc<Statement>),
+    Function(Closure<'a>),
+    /// 宿主程序提供的函数
+    Native(Native<'a>),
+}
+
+/// 函数值：函数声明，以及声明时所在的环境
+#[derive(Clone)]
+pub struct Closure<'a> {
+    pub declaration: Rc<Statement>,
+    pub closure: Rc<RefCell<Environment<'a>>>,
+}
+
+impl<'a> Closure<'a> {
+    pub fn name(&self) -> &str {
+        match self.declaration.as_ref() {
+            Statement::Function(name, ..) => &name.lexeme,
+            _ => "anonymous",
+        }
+    }
+}
+
+/// 本地函数的实现，出错时返回错误信息
+pub type NativeFn<'a> = dyn Fn(&[Rc<UnionObject<'a>>]) -> Result<Rc<UnionObject<'a>>, String> + 'a;
+
+/// 本地函数：名字、参数个数以及 Rust 实现
+#[derive(Clone)]
+pub struct Native<'a> {
+    pub name: String,
+    pub arity: usize,
+    pub function: Rc<NativeFn<'a>>,
+}
+
+impl<'a> UnionObject<'a> {
+    /// 不同类型的值总是不相等，函数按引用比较
+    pub fn equals(&self, other: &UnionObject<'a>) -> bool {
+        match (self.object(), other.object()) {
+            (Some(l), Some(r)) => l == r,
+            (None, None) => match (self, other) {
+                (UnionObject::Function(l), UnionObject::Function(r)) => {
+                    Rc::ptr_eq(&l.declaration, &r.declaration) && Rc::ptr_eq(&l.closure, &r.closure)
+                }
+                (UnionObject::Native(l), UnionObject::Native(r)) => {
+                    Rc::ptr_eq(&l.function, &r.function)
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn object(&self) -> Option<&Object> {
+        match self {
+            UnionObject::Value(v) => Some(v),
+            UnionObject::Reference(v) => Some(v),
+            UnionObject::Function(_) | UnionObject::Native(_) => None,
+        }
+    }
+
+    /// 只有 false 和 nil 为假，其余都为真
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            UnionObject::Value(Object::Bool(v)) | UnionObject::Reference(Object::Bool(v)) => *v,
+            UnionObject::Value(Object::Nil) | UnionObject::Reference(Object::Nil) => false,
+            _ => true,
+        }
+    }
+}
+
+// 环境里可能包含函数自身，不能直接打印环境
+impl<'a> Debug for Closure<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
+
+impl<'a> Debug for Native<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl<'a> Display for UnionObject<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnionObject::Value(v) => write!(f, "{}", v),
+            UnionObject::Reference(v) => write!(f, "{}", v),
+            UnionObject::Function(closure) => write!(f, "{:?}", closure),
+            UnionObject::Native(native) => write!(f, "{:?}", native),
+        }
+    }
 }
 
 impl From<String> for Object {
@@ -99,9 +194,9 @@ impl From<bool> for Object {
     }
 }
 
-impl Into<f32> for Object {
-    fn into(self) -> f32 {
-        match self {
+impl From<Object> for f32 {
+    fn from(v: Object) -> Self {
+        match v {
             Object::Digit(v) => v,
             _ => todo!(),
         }
@@ -120,12 +215,17 @@ impl<'a> From<&'a Object> for Rc<UnionObject<'a>> {
     }
 }
 
-impl<'a> Into<Object> for Rc<UnionObject<'a>> {
-    fn into(self) -> Object {
-        match self.as_ref() {
-            UnionObject::Value(v) => v.to_owned(),
-            UnionObject::Reference(v) => todo!(),
-            UnionObject::Function(_) => todo!(),
+/// 函数没有对应的 Object，转换失败
+impl<'a> TryFrom<Rc<UnionObject<'a>>> for Object {
+    type Error = String;
+
+    fn try_from(v: Rc<UnionObject<'a>>) -> Result<Self, Self::Error> {
+        match v.as_ref() {
+            UnionObject::Value(v) => Ok(v.to_owned()),
+            UnionObject::Reference(v) => Ok((*v).to_owned()),
+            UnionObject::Function(_) | UnionObject::Native(_) => {
+                Err(format!("expected a value but got {}", v))
+            }
         }
     }
 }
@@ -136,6 +236,7 @@ impl Display for Object {
             Object::String(v) => write!(f, "{}", v),
             Object::Digit(v) => write!(f, "{}", v),
             Object::Bool(v) => write!(f, "{}", v),
+            Object::Nil => write!(f, "nil"),
             Object::Placeholder => write!(f, ""),
         };
 
@@ -166,12 +267,59 @@ pub static Keywords: phf::Map<&'static str, TokenType> = phf_map! {
     "function" => FUNCTION
 };
 
+/// 源码中的一段区间：字节偏移、字节长度，以及起始位置的行列号（从 1 开始）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub offset: usize,
+    pub len: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(offset: usize, len: usize, line: usize, column: usize) -> Self {
+        Span {
+            offset,
+            len,
+            line,
+            column,
+        }
+    }
+
+    /// 结束位置的字节偏移（不包含）
+    pub fn end(&self) -> usize {
+        self.offset + self.len
+    }
+
+    /// 从当前 span 开始，一直覆盖到 other 结束
+    pub fn to(self, other: Span) -> Span {
+        let end = self.end().max(other.end());
+        Span {
+            len: end - self.offset,
+            ..self
+        }
+    }
+}
+
+/// 不影响语法的内容，只在 lossless 模式下保留
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trivia {
+    /// 连续的空格、制表符和 \r
+    Whitespace(String),
+    Newline,
+    /// 包含开头的 `//`，不包含结尾的换行
+    Comment(String),
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub tag: TokenType,
     pub lexeme: String,
     pub literal: Object,
     pub line: usize,
+    pub span: Span,
+    /// token 之前的 trivia，把所有 token 的 trivia 和 lexeme 依次拼接即为原始源码
+    pub trivia: Vec<Trivia>,
 }
 
 impl Token {
@@ -186,6 +334,16 @@ impl Token {
             lexeme: lexeme.to_string(),
             literal,
             line,
+            span: Span {
+                line,
+                ..Span::default()
+            },
+            trivia: vec![],
         }
     }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
 }
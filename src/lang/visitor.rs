@@ -0,0 +1,262 @@
+// 遍历 AST 的 visitor。
+//
+// 与书中的 Visitor<R> 相同，visit_* 的返回值由实现者决定：
+// 分析类的 visitor 使用 ()，解释器返回求得的值或者 Result<(), _>。
+// 返回 () 的 visitor 只需要处理关心的节点，其余交给 walk_* 函数继续向下遍历。
+// 不可变版本用于分析和执行，可变版本用于改写 AST（例如 Resolver 写入作用域深度）。
+
+use super::ast::{Expression, Statement};
+
+/// 执行语句的结果，visit_stmts 遇到中断时不再访问后面的语句
+pub trait Flow {
+    /// 全部语句正常结束
+    fn done() -> Self;
+    fn is_break(&self) -> bool;
+}
+
+impl Flow for () {
+    fn done() -> Self {}
+
+    fn is_break(&self) -> bool {
+        false
+    }
+}
+
+/// Err 表示中断，例如运行时错误或者 return
+impl<E> Flow for Result<(), E> {
+    fn done() -> Self {
+        Ok(())
+    }
+
+    fn is_break(&self) -> bool {
+        self.is_err()
+    }
+}
+
+pub trait ExprVisitor<'ast> {
+    type Output;
+
+    fn visit_expr(&mut self, expr: &'ast Expression) -> Self::Output;
+}
+
+pub trait StmtVisitor<'ast>: ExprVisitor<'ast> {
+    type StmtOutput: Flow;
+
+    fn visit_stmt(&mut self, stmt: &'ast Statement) -> Self::StmtOutput;
+
+    /// 依次访问语句列表，块、函数体以及整个程序都会经过这里
+    fn visit_stmts(&mut self, statements: &'ast [Statement]) -> Self::StmtOutput {
+        for stmt in statements {
+            let output = self.visit_stmt(stmt);
+            if output.is_break() {
+                return output;
+            }
+        }
+        Self::StmtOutput::done()
+    }
+}
+
+pub trait ExprVisitorMut {
+    type Output;
+
+    fn visit_expr_mut(&mut self, expr: &mut Expression) -> Self::Output;
+}
+
+pub trait StmtVisitorMut: ExprVisitorMut {
+    type StmtOutput: Flow;
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Statement) -> Self::StmtOutput;
+
+    fn visit_stmts_mut(&mut self, statements: &mut [Statement]) -> Self::StmtOutput {
+        for stmt in statements {
+            let output = self.visit_stmt_mut(stmt);
+            if output.is_break() {
+                return output;
+            }
+        }
+        Self::StmtOutput::done()
+    }
+}
+
+/// 访问表达式的全部子表达式
+pub fn walk_expr<'ast, V>(visitor: &mut V, expr: &'ast Expression)
+where
+    V: ExprVisitor<'ast, Output = ()> + ?Sized,
+{
+    match expr {
+        Expression::Assignment(_, value, _) => visitor.visit_expr(value),
+        Expression::Unary(_, right) | Expression::Grouping(right, _) => visitor.visit_expr(right),
+        Expression::Binary(left, _, right) | Expression::Logical(left, _, right) => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        Expression::Call(callee, _, arguments) => {
+            visitor.visit_expr(callee);
+            for argument in arguments {
+                visitor.visit_expr(argument);
+            }
+        }
+        Expression::Literal(..) | Expression::Var(..) | Expression::Mark => {}
+    }
+}
+
+/// 访问语句中的全部子语句和表达式
+pub fn walk_stmt<'ast, V>(visitor: &mut V, stmt: &'ast Statement)
+where
+    V: StmtVisitor<'ast, Output = (), StmtOutput = ()> + ?Sized,
+{
+    match stmt {
+        Statement::Print(expr, _) | Statement::Expression(expr) => visitor.visit_expr(expr),
+        Statement::Var(_, initializer, _) => {
+            if let Some(initializer) = initializer {
+                visitor.visit_expr(initializer);
+            }
+        }
+        Statement::Block(statements, _) | Statement::Function(_, _, statements, _) => {
+            visitor.visit_stmts(statements)
+        }
+        Statement::If(_, condition, then_stmt, else_stmt) => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(then_stmt);
+            if let Some(else_stmt) = else_stmt {
+                visitor.visit_stmt(else_stmt);
+            }
+        }
+        Statement::Return(_, value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        Statement::While(_, condition, body) => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(body);
+        }
+    }
+}
+
+pub fn walk_expr_mut<V>(visitor: &mut V, expr: &mut Expression)
+where
+    V: ExprVisitorMut<Output = ()> + ?Sized,
+{
+    match expr {
+        Expression::Assignment(_, value, _) => visitor.visit_expr_mut(value),
+        Expression::Unary(_, right) | Expression::Grouping(right, _) => {
+            visitor.visit_expr_mut(right)
+        }
+        Expression::Binary(left, _, right) | Expression::Logical(left, _, right) => {
+            visitor.visit_expr_mut(left);
+            visitor.visit_expr_mut(right);
+        }
+        Expression::Call(callee, _, arguments) => {
+            visitor.visit_expr_mut(callee);
+            for argument in arguments {
+                visitor.visit_expr_mut(argument);
+            }
+        }
+        Expression::Literal(..) | Expression::Var(..) | Expression::Mark => {}
+    }
+}
+
+pub fn walk_stmt_mut<V>(visitor: &mut V, stmt: &mut Statement)
+where
+    V: StmtVisitorMut<Output = (), StmtOutput = ()> + ?Sized,
+{
+    match stmt {
+        Statement::Print(expr, _) | Statement::Expression(expr) => visitor.visit_expr_mut(expr),
+        Statement::Var(_, initializer, _) => {
+            if let Some(initializer) = initializer {
+                visitor.visit_expr_mut(initializer);
+            }
+        }
+        Statement::Block(statements, _) | Statement::Function(_, _, statements, _) => {
+            visitor.visit_stmts_mut(statements)
+        }
+        Statement::If(_, condition, then_stmt, else_stmt) => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_stmt_mut(then_stmt);
+            if let Some(else_stmt) = else_stmt {
+                visitor.visit_stmt_mut(else_stmt);
+            }
+        }
+        Statement::Return(_, value) => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        Statement::While(_, condition, body) => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_stmt_mut(body);
+        }
+    }
+}
+
+#[test]
+fn visit_every_node() {
+    use super::{lexer::Lexer, parser::Parser, token::Token};
+
+    #[derive(Default)]
+    struct Names(Vec<String>);
+
+    impl<'ast> ExprVisitor<'ast> for Names {
+        type Output = ();
+
+        fn visit_expr(&mut self, expr: &'ast Expression) {
+            if let Expression::Var(name, _) = expr {
+                self.0.push(name.lexeme.clone());
+            }
+            walk_expr(self, expr);
+        }
+    }
+    impl<'ast> StmtVisitor<'ast> for Names {
+        type StmtOutput = ();
+
+        fn visit_stmt(&mut self, stmt: &'ast Statement) {
+            walk_stmt(self, stmt);
+        }
+    }
+
+    /// 把所有变量改名
+    struct Rename;
+
+    impl ExprVisitorMut for Rename {
+        type Output = ();
+
+        fn visit_expr_mut(&mut self, expr: &mut Expression) {
+            if let Expression::Var(name, _) = expr {
+                *name = Token::new(
+                    name.tag,
+                    name.lexeme.to_uppercase(),
+                    name.literal.clone(),
+                    name.line,
+                );
+            }
+            walk_expr_mut(self, expr);
+        }
+    }
+    impl StmtVisitorMut for Rename {
+        type StmtOutput = ();
+
+        fn visit_stmt_mut(&mut self, stmt: &mut Statement) {
+            walk_stmt_mut(self, stmt);
+        }
+    }
+
+    let (tokens, _) = Lexer::new(String::from(
+        "fun f(a) {
+            if (a) return b(c, -d);
+            while (e or (f)) { var g = h = i; }
+        }
+        print j == k;",
+    ))
+    .scan_tokens();
+    let (mut statements, _) = Parser::new(tokens).parse();
+
+    let mut names = Names::default();
+    names.visit_stmts(&statements);
+    assert_eq!(names.0, vec!["a", "b", "c", "d", "e", "f", "i", "j", "k"]);
+
+    Rename.visit_stmts_mut(&mut statements);
+    let mut names = Names::default();
+    names.visit_stmts(&statements);
+    assert_eq!(names.0, vec!["A", "B", "C", "D", "E", "F", "I", "J", "K"]);
+}
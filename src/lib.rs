@@ -1,2 +1,7 @@
 mod lang;
 mod playground;
+
+// 嵌入脚本只需要这几个类型
+pub use lang::engine::{Engine, Error, Function, Value};
+// 命令行工具用到的部分
+pub use lang::{diagnostic, dump, formatter, lint, repl};
@@ -1 +1,313 @@
-fn main() {}
+// crafting [--dump <sexpr|json|lox>] [-A | -W | -D <lint>]... [file | -]
+//
+// 运行 Lox 脚本，文件为 - 时从标准输入读取；没有参数时进入交互模式，
+// 支持行编辑、历史记录、补全以及 :help 列出的命令。
+// 运行脚本前先做代码检查，-A / -W / -D 把指定的 lint 设为 allow / warn / deny，
+// 存在 deny 级别的结果时不执行脚本。
+// 退出码与书中相同：编译错误 65，运行时错误 70，读取文件失败 74。
+
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::PathBuf,
+    process,
+};
+
+use crafting::{
+    diagnostic::{Diagnostic, Style},
+    dump,
+    lint::{Level, Lint, LintConfig},
+    repl::{self, Repl},
+    Engine, Error,
+};
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Editor, Helper,
+};
+
+/// sysexits.h 中的退出码
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_SOFTWARE: i32 = 70;
+const EX_IOERR: i32 = 74;
+
+const USAGE: &str = "usage: crafting [--dump <sexpr|json|lox>] [-A | -W | -D <lint>]... [file | -]";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dump {
+    SExpr,
+    Json,
+    /// 由 AST 还原的规范格式源码
+    Lox,
+}
+
+struct Options {
+    /// 只输出 AST，不执行
+    dump: Option<Dump>,
+    lints: LintConfig,
+    path: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        dump: None,
+        lints: LintConfig::new(),
+        path: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dump" => {
+                options.dump = match args.next().as_deref() {
+                    Some("sexpr") => Some(Dump::SExpr),
+                    Some("json") => Some(Dump::Json),
+                    Some("lox") => Some(Dump::Lox),
+                    _ => return Err("--dump expects 'sexpr', 'json' or 'lox'".to_string()),
+                }
+            }
+            flag @ ("-A" | "-W" | "-D") => {
+                let level = match flag {
+                    "-A" => Level::Allow,
+                    "-W" => Level::Warn,
+                    _ => Level::Deny,
+                };
+                let name = args.next().unwrap_or_default();
+                match Lint::from_name(&name) {
+                    Some(lint) => options.lints.set(lint, level),
+                    None => return Err(format!("unknown lint '{}'", name)),
+                };
+            }
+            "-h" | "--help" => return Err(String::new()),
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown option '{}'", flag))
+            }
+            path if options.path.is_none() => options.path = Some(path.to_string()),
+            _ => return Err("only one file can be given".to_string()),
+        }
+    }
+    Ok(options)
+}
+
+/// 读取文件，- 表示标准输入
+fn read_source(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+fn report(file: &str, source: &str, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprint!("{}", diagnostic.render(file, source, Style::Plain));
+    }
+}
+
+/// 交互模式下的补全：关键字以及当前的全局变量
+struct LoxHelper {
+    names: Vec<String>,
+}
+
+impl Completer for LoxHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = repl::word_start(line, pos);
+        let prefix = &line[start..pos];
+        let candidates = self
+            .names
+            .iter()
+            .filter(|name| !prefix.is_empty() && name.starts_with(prefix))
+            .cloned()
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for LoxHelper {
+    type Hint = String;
+}
+
+impl Highlighter for LoxHelper {}
+
+impl Validator for LoxHelper {}
+
+impl Helper for LoxHelper {}
+
+const COMMANDS: &str = ":env               list global bindings
+:ast <source>      show the parse tree
+:tokens <source>   show the lexer output
+:load <file>       run a file in this session
+:reset             forget all definitions
+:help              show this message";
+
+/// 历史记录保存在 ~/.crafting_history
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".crafting_history"))
+}
+
+/// 以 : 开头的命令
+fn run_command(repl: &mut Repl, line: &str) {
+    let (command, argument) = match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+    };
+    match command {
+        ":env" => {
+            for (name, value) in repl.globals() {
+                println!("{} = {}", name, value);
+            }
+        }
+        ":ast" => match dump::sexpr_source(argument) {
+            Ok(out) => print!("{}", out),
+            Err(diagnostics) => report("<repl>", argument, &diagnostics),
+        },
+        ":tokens" => {
+            let (out, diagnostics) = dump::tokens(argument);
+            print!("{}", out);
+            report("<repl>", argument, &diagnostics);
+        }
+        ":load" if !argument.is_empty() => match read_source(argument) {
+            Ok(source) => {
+                if let Err(diagnostics) = repl.eval(&source) {
+                    report(argument, &source, &diagnostics);
+                }
+            }
+            Err(e) => eprintln!("error: can't read {}: {}", argument, e),
+        },
+        ":reset" => repl.reset(),
+        ":help" => println!("{}", COMMANDS),
+        _ => eprintln!("error: unknown command '{}', try :help", line),
+    }
+}
+
+/// 交互模式，输入不完整时继续读取下一行，直到遇到 EOF
+fn run_prompt() {
+    let mut repl = Repl::new();
+    let mut editor = match Editor::<LoxHelper, DefaultHistory>::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(EX_IOERR);
+        }
+    };
+    editor.set_helper(Some(LoxHelper {
+        names: repl.completions(),
+    }));
+    let history = history_path();
+    if let Some(path) = &history {
+        // 第一次运行时文件还不存在
+        editor.load_history(path).ok();
+    }
+
+    let mut source = String::new();
+    loop {
+        let prompt = if source.is_empty() { "> " } else { "... " };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            // Ctrl-C 放弃当前输入
+            Err(ReadlineError::Interrupted) => {
+                source.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                process::exit(EX_IOERR);
+            }
+        };
+        editor.add_history_entry(line.as_str()).ok();
+
+        if source.is_empty() && line.trim_start().starts_with(':') {
+            run_command(&mut repl, line.trim());
+        } else {
+            source.push_str(&line);
+            source.push('\n');
+            if repl::is_incomplete(&source) {
+                continue;
+            }
+            match repl.eval(&source) {
+                Ok(values) => values.iter().for_each(|value| println!("{}", value)),
+                Err(diagnostics) => report("<repl>", &source, &diagnostics),
+            }
+            source.clear();
+        }
+        if let Some(helper) = editor.helper_mut() {
+            helper.names = repl.completions();
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Err(e) = editor.save_history(path) {
+            eprintln!("error: can't save history: {}", e);
+        }
+    }
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("error: {}", message);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(EX_USAGE);
+        }
+    };
+
+    let path = match options.path {
+        Some(path) => path,
+        None if options.dump.is_none() => return run_prompt(),
+        None => {
+            eprintln!("error: --dump needs a file");
+            eprintln!("{}", USAGE);
+            process::exit(EX_USAGE);
+        }
+    };
+    let file = if path == "-" {
+        "<stdin>"
+    } else {
+        path.as_str()
+    };
+    let source = match read_source(&path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("error: can't read {}: {}", file, e);
+            process::exit(EX_IOERR);
+        }
+    };
+
+    if let Some(format) = options.dump {
+        let result = match format {
+            Dump::SExpr => dump::sexpr_source(&source),
+            Dump::Json => dump::json_source(&source).map(|out| out + "\n"),
+            Dump::Lox => dump::lox_source(&source),
+        };
+        match result {
+            Ok(out) => print!("{}", out),
+            Err(diagnostics) => {
+                report(file, &source, &diagnostics);
+                process::exit(EX_DATAERR);
+            }
+        }
+        return;
+    }
+
+    let mut engine = Engine::new().with_lints(options.lints);
+    let result = engine.eval(&source);
+    report(file, &source, &engine.take_warnings());
+    if let Err(error) = result {
+        report(file, &source, error.diagnostics());
+        process::exit(match error {
+            Error::Runtime(_) => EX_SOFTWARE,
+            _ => EX_DATAERR,
+        });
+    }
+}
@@ -1 +1,2 @@
+#[allow(dead_code, clippy::no_effect)]
 mod lifetime;